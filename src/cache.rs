@@ -0,0 +1,186 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Hyper
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+
+// SHA-2
+use sha2::{Digest, Sha256};
+
+// Fetcher
+use fetcher::{Error, Fetcher, Page, Result};
+
+/// A page stored on disk together with where and when it was fetched.
+pub struct Entry {
+    pub url: String,
+    pub fetched_at: u64,
+    pub page: Page,
+}
+
+/// Numbers the temporary files of this process.
+static NEXT_TEMPORARY: AtomicUsize = AtomicUsize::new(0);
+
+/// A path next to `path` to write it under before moving it into place.
+/// Every call gets its own, so workers writing the same file at once do not
+/// move each other's temporary file away.
+pub fn temporary_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}-{}.tmp", process::id(), NEXT_TEMPORARY.fetch_add(1, Ordering::Relaxed)));
+    path.with_file_name(name)
+}
+
+/// Writes an entry as its URL, fetch time, status and headers, a blank line
+/// and then the raw body. The file is written next to `path` first and moved
+/// into place so readers never see half an entry.
+pub fn write_entry(path: &Path, entry: &Entry) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut meta = format!("{}\n{}\n{}\n", entry.url, entry.fetched_at, entry.page.status.to_u16());
+    for header in entry.page.headers.iter() {
+        meta.push_str(&format!("{}: {}\n", header.name(), header.value_string()));
+    }
+    meta.push('\n');
+
+    let tmp_path = temporary_path(path);
+    {
+        let mut f = File::create(&tmp_path)?;
+        f.write_all(meta.as_bytes())?;
+        f.write_all(&entry.page.body)?;
+    }
+    fs::rename(&tmp_path, path)
+}
+
+/// Reads an entry written by `write_entry`.
+pub fn read_entry(path: &Path) -> io::Result<Entry> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("malformed entry {}", path.display()));
+
+    let split = data.windows(2).position(|w| w == b"\n\n").ok_or_else(invalid)?;
+    let meta = str::from_utf8(&data[..split + 1]).map_err(|_| invalid())?;
+    let mut lines = meta.lines();
+
+    let url = lines.next().ok_or_else(invalid)?.to_string();
+    let fetched_at = lines.next().and_then(|line| line.parse::<u64>().ok()).ok_or_else(invalid)?;
+    let status = lines.next().and_then(|line| line.parse::<u16>().ok()).ok_or_else(invalid)?;
+
+    let mut headers = Headers::new();
+    for line in lines {
+        if let Some(colon) = line.find(':') {
+            headers.set_raw(line[..colon].to_string(), vec![line[colon + 1..].trim().as_bytes().to_vec()]);
+        }
+    }
+
+    Ok(Entry {
+        url,
+        fetched_at,
+        page: Page {
+            status: StatusCode::from_u16(status),
+            headers,
+            body: data[split + 2..].to_vec(),
+        },
+    })
+}
+
+/// File name under which the page of `url` is stored.
+pub fn key(url: &str) -> String {
+    format!("{:x}", Sha256::digest(url.as_bytes()))
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Wraps another fetcher with an on-disk response cache.
+///
+/// Entries are keyed by the SHA-256 of the URL. Fresh entries are served
+/// without touching the network; stale ones are revalidated with their
+/// `ETag` and `Last-Modified` headers. In offline mode only the cache is
+/// consulted, whatever the age of the entries.
+pub struct CachingFetcher<F> {
+    inner: F,
+    dir: PathBuf,
+    ttl: Duration,
+    offline: bool,
+}
+
+impl<F: Fetcher> CachingFetcher<F> {
+    pub fn new(inner: F, dir: PathBuf, ttl: Duration, offline: bool) -> CachingFetcher<F> {
+        CachingFetcher {
+            inner,
+            dir,
+            ttl,
+            offline,
+        }
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        let key = key(url);
+        self.dir.join(&key[..2]).join(&key)
+    }
+
+    fn store(&self, path: &Path, entry: &Entry) {
+        if let Err(error) = write_entry(path, entry) {
+            println!("error: cache: failed to store {}: {}", entry.url, error);
+        }
+    }
+}
+
+impl<F: Fetcher> Fetcher for CachingFetcher<F> {
+    fn fetch(&self, url: &str, headers: &Headers) -> Result<Page> {
+        let path = self.path(url);
+        let cached = read_entry(&path).ok();
+
+        let mut headers = headers.clone();
+        match cached {
+            Some(ref entry) => {
+                if self.offline || now_secs().saturating_sub(entry.fetched_at) < self.ttl.as_secs() {
+                    return Ok(entry.page.clone());
+                }
+
+                if let Some(etag) = entry.page.headers.get_raw("ETag") {
+                    headers.set_raw("If-None-Match", etag.to_vec());
+                }
+                if let Some(last_modified) = entry.page.headers.get_raw("Last-Modified") {
+                    headers.set_raw("If-Modified-Since", last_modified.to_vec());
+                }
+            },
+            None => if self.offline {
+                return Err(Error::Missing(url.to_string()));
+            },
+        }
+
+        let page = self.inner.fetch(url, &headers)?;
+
+        if page.status == StatusCode::NotModified {
+            if let Some(mut entry) = cached {
+                entry.fetched_at = now_secs();
+                self.store(&path, &entry);
+                return Ok(entry.page);
+            }
+        }
+
+        if !page.status.is_success() {
+            return Ok(page);
+        }
+
+        let entry = Entry {
+            url: url.to_string(),
+            fetched_at: now_secs(),
+            page,
+        };
+        self.store(&path, &entry);
+        Ok(entry.page)
+    }
+}
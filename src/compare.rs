@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+// TOML
+use toml;
+
+// Spider
+use country::Country;
+use csv;
+use price::{parse_price, Price};
+
+/// Exchange rates into a common `base` currency: one unit of each currency
+/// in `rates` is worth that many units of `base`.
+#[derive(Deserialize)]
+pub struct Rates {
+    pub base: String,
+    pub rates: BTreeMap<String, f64>,
+}
+
+/// Name and displayed price of an item in one market.
+pub struct Listing {
+    pub name: String,
+    pub price: String,
+    /// The `Price Amount` and `Price Currency` columns, which files written
+    /// before they existed do not have.
+    pub amount: Option<f64>,
+    pub currency: String,
+}
+
+impl Listing {
+    /// The price read by the crawl, or failing that parsed from the displayed
+    /// price the way `country` writes it.
+    fn price(&self, country: &Country) -> Option<Price> {
+        match self.amount {
+            Some(amount) => Some(Price {
+                amount,
+                currency: if self.currency.is_empty() { country.currency.clone() } else { self.currency.clone() },
+            }),
+            None => parse_price(&self.price, country),
+        }
+    }
+}
+
+fn read_to_string(path: &Path) -> Result<String, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|error| format!("{}: {}", path.display(), error))?;
+    Ok(contents)
+}
+
+/// Loads exchange rates from a TOML file such as
+///
+/// ```toml
+/// base = "SGD"
+///
+/// [rates]
+/// SGD = 1.0
+/// MYR = 0.31
+/// ```
+pub fn load_rates(path: &Path) -> Result<Rates, String> {
+    let source = read_to_string(path)?;
+    toml::from_str(&source).map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+/// Reads the listings of a CSV written by the file output, keyed by item number.
+pub fn read_listings(path: &Path) -> Result<BTreeMap<String, Listing>, String> {
+    let records = csv::parse(&read_to_string(path)?);
+
+    let header = match records.first() {
+        Some(header) => header,
+        None => return Ok(BTreeMap::new()),
+    };
+    let column = |name: &str| header.iter().position(|field| field.trim() == name)
+        .ok_or_else(|| format!("{}: no {} column", path.display(), name));
+    let id_column = column("Item Number")?;
+    let name_column = column("Name")?;
+    let price_column = column("Price")?;
+    let amount_column = column("Price Amount").ok();
+    let currency_column = column("Price Currency").ok();
+
+    let mut listings = BTreeMap::new();
+    for record in &records[1..] {
+        let field = |column: usize| record.get(column).cloned().unwrap_or_default();
+        let optional_field = |column: Option<usize>| column.map(field).unwrap_or_default();
+
+        let id = field(id_column);
+        if id.is_empty() {
+            continue;
+        }
+
+        listings.insert(id, Listing {
+            name: field(name_column),
+            price: field(price_column),
+            amount: optional_field(amount_column).parse().ok(),
+            currency: optional_field(currency_column),
+        });
+    }
+
+    Ok(listings)
+}
+
+/// Writes one row per item number found in any market, with the name, local
+/// price and converted price of the item in every market.
+pub fn write_comparison(output: &str, markets: &[(&Country, BTreeMap<String, Listing>)], rates: &Rates) -> Result<(), String> {
+    let ids: BTreeSet<&String> = markets.iter().flat_map(|market| market.1.keys()).collect();
+
+    let mut header = vec!["Item Number".to_string()];
+    for &(country, _) in markets {
+        header.push(format!("{} Name", country.code));
+        header.push(format!("{} Price", country.code));
+        header.push(format!("{} Price ({})", country.code, rates.base));
+    }
+
+    let mut contents = csv::format_record(&header.iter().map(|field| field.as_str()).collect::<Vec<&str>>());
+
+    for id in ids {
+        let mut row = vec![id.clone()];
+
+        for &(country, ref listings) in markets {
+            match listings.get(id) {
+                Some(listing) => {
+                    let price = listing.price(country);
+                    let converted = match price.as_ref().and_then(|price| rates.rates.get(&price.currency).map(|rate| price.amount * rate)) {
+                        Some(amount) => format!("{:.2}", amount),
+                        None => String::new(),
+                    };
+                    // Pages that only carry structured data have no displayed price.
+                    let local = match price {
+                        Some(ref price) if listing.price.is_empty() => format!("{:.2} {}", price.amount, price.currency),
+                        _ => listing.price.clone(),
+                    };
+
+                    row.push(listing.name.clone());
+                    row.push(local);
+                    row.push(converted);
+                },
+                None => {
+                    row.push(String::new());
+                    row.push(String::new());
+                    row.push(String::new());
+                },
+            }
+        }
+
+        contents.push_str(&csv::format_record(&row.iter().map(|field| field.as_str()).collect::<Vec<&str>>()));
+    }
+
+    File::create(output)
+        .and_then(|mut f| f.write_all(contents.as_bytes()))
+        .map_err(|error| format!("{}: {}", output, error))
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+// Serde
+use serde_json;
+use toml;
+
+// Spider
+use dimensions::Labels;
+
+/// Catalogue compiled into the binary, used unless `--countries` is given.
+const DEFAULT_COUNTRIES: &str = include_str!("../countries.toml");
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Country {
+    pub name: String,
+    pub code: String,
+    pub language: String,
+    pub path: String,
+    pub currency: String,
+    /// Locale of the site, for reference only; numbers are read with
+    /// `decimal` and `grouping`.
+    pub locale: String,
+    /// Decimal point of the prices and measurements on the site.
+    #[serde(default = "default_decimal")]
+    pub decimal: char,
+    /// Thousands separator of the prices and measurements on the site.
+    #[serde(default = "default_grouping")]
+    pub grouping: char,
+    #[serde(default = "default_selectors")]
+    pub selectors: String,
+    /// Labels of the measurements on product pages, English by default.
+    #[serde(default)]
+    pub dimension_labels: Labels,
+    /// Units the site spells its own way, in lower case and without a
+    /// trailing dot, mapped to the symbol the spider converts from, e.g.
+    /// "ซม" to "cm".
+    #[serde(default)]
+    pub dimension_units: BTreeMap<String, String>,
+}
+
+fn default_decimal() -> char {
+    '.'
+}
+
+fn default_grouping() -> char {
+    ','
+}
+
+fn default_selectors() -> String {
+    "default".to_string()
+}
+
+#[derive(Deserialize)]
+struct Catalogue {
+    country: Vec<Country>,
+}
+
+/// Returns the built-in country catalogue.
+pub fn default_countries() -> Vec<Country> {
+    parse_countries(DEFAULT_COUNTRIES).expect("built-in countries.toml is invalid")
+}
+
+/// Parses a TOML catalogue made of `[[country]]` tables.
+pub fn parse_countries(source: &str) -> Result<Vec<Country>, String> {
+    let catalogue: Catalogue = toml::from_str(source).map_err(|error| error.to_string())?;
+    Ok(catalogue.country)
+}
+
+/// Loads a catalogue from a TOML file, or from a JSON file holding the same
+/// structure when the file name ends in `.json`.
+pub fn load_countries(path: &Path) -> Result<Vec<Country>, String> {
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut source))
+        .map_err(|error| format!("{}: {}", path.display(), error))?;
+
+    if path.extension().is_some_and(|ext| ext == "json") {
+        let catalogue: Catalogue = serde_json::from_str(&source).map_err(|error| format!("{}: {}", path.display(), error))?;
+        Ok(catalogue.country)
+    } else {
+        parse_countries(&source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+
+/// Finds a country by its code, e.g. `sg-en`.
+pub fn find_country<'a>(countries: &'a [Country], code: &str) -> Option<&'a Country> {
+    countries.iter().find(|country| country.code == code)
+}
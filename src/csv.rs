@@ -0,0 +1,49 @@
+/// Formats one CSV record, quoting every field and doubling embedded quotes.
+pub fn format_record(fields: &[&str]) -> String {
+    let quoted: Vec<String> = fields.iter().map(|field| format!("\"{}\"", field.replace('"', "\"\""))).collect();
+    format!("{}\n", quoted.join(","))
+}
+
+/// Parses CSV text into records. Fields may be quoted, in which case they
+/// can hold commas, newlines and doubled quotes.
+pub fn parse(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                field.push(c);
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_quotes = true,
+            ',' => record.push(field.split_off(0)),
+            '\r' => {},
+            '\n' => {
+                record.push(field.split_off(0));
+                records.push(record.split_off(0));
+            },
+            _ => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records
+}
@@ -0,0 +1,154 @@
+// Spider
+use country::Country;
+use price;
+
+/// Measurements of a product, normalised to centimetres, kilograms and litres.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Dimensions {
+    pub width: Option<f64>,
+    pub depth: Option<f64>,
+    pub height: Option<f64>,
+    pub length: Option<f64>,
+    pub weight: Option<f64>,
+    pub volume: Option<f64>,
+}
+
+/// One of the packages a product ships in. `quantity` is how many of this
+/// package make up one product.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Package {
+    pub item_number: String,
+    pub quantity: u32,
+    pub dimensions: Dimensions,
+}
+
+/// The labels a site writes before each measurement, e.g. "Width" or
+/// "Lebar". A line counts only when its whole label is one of them, ignoring
+/// case, so "Package weight" or "Mattress length" are not taken for the
+/// product's own.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Labels {
+    pub width: Vec<String>,
+    pub depth: Vec<String>,
+    pub height: Vec<String>,
+    pub length: Vec<String>,
+    pub weight: Vec<String>,
+    pub volume: Vec<String>,
+}
+
+impl Default for Labels {
+    fn default() -> Labels {
+        let labels = |labels: &[&str]| labels.iter().map(|label| label.to_string()).collect();
+
+        Labels {
+            width: labels(&["Width"]),
+            depth: labels(&["Depth"]),
+            height: labels(&["Height", "Min. height", "Max. height"]),
+            length: labels(&["Length"]),
+            weight: labels(&["Weight", "Net weight"]),
+            volume: labels(&["Volume"]),
+        }
+    }
+}
+
+fn is_one_of(label: &str, labels: &[String]) -> bool {
+    labels.iter().any(|known| known.to_lowercase() == label)
+}
+
+/// Converts one unit of a measurement into the unit it is stored in.
+type Factor = fn(&str) -> Option<f64>;
+
+/// Centimetres per unit of length.
+fn centimetres(unit: &str) -> Option<f64> {
+    match unit {
+        "mm" => Some(0.1),
+        "cm" => Some(1.0),
+        "m" => Some(100.0),
+        "in" | "inch" | "inches" | "\"" | "″" => Some(2.54),
+        "ft" | "feet" | "'" | "′" => Some(30.48),
+        _ => None,
+    }
+}
+
+/// Kilograms per unit of weight.
+fn kilograms(unit: &str) -> Option<f64> {
+    match unit {
+        "g" => Some(0.001),
+        "kg" => Some(1.0),
+        "oz" => Some(0.028_349_523_125),
+        "lb" | "lbs" => Some(0.453_592_37),
+        _ => None,
+    }
+}
+
+/// Litres per unit of volume.
+fn litres(unit: &str) -> Option<f64> {
+    match unit {
+        "ml" => Some(0.001),
+        "cl" => Some(0.01),
+        "dl" => Some(0.1),
+        "l" => Some(1.0),
+        "gal" => Some(3.785_411_784),
+        _ => None,
+    }
+}
+
+/// Splits a value such as "90 cm" or "35 \"" into its amount and unit.
+fn parse_measure(value: &str, country: &Country) -> Option<(f64, String)> {
+    let start = value.find(|c: char| c.is_ascii_digit())?;
+    let rest = &value[start..];
+    let end = rest.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == ',' || c == country.grouping)).unwrap_or(rest.len());
+
+    let amount = price::parse_amount(&rest[..end], country.decimal, country.grouping)?;
+    let unit: String = rest[end..].trim_start().chars()
+        .take_while(|c| c.is_alphabetic() || "\"″'′".contains(*c))
+        .collect();
+
+    Some((amount, unit.to_lowercase()))
+}
+
+/// Reads the measurement section of a product page, one "Label: value unit"
+/// per line, e.g. "Width: 90 cm". Labels are those of `country`; the first
+/// line naming a measurement wins, so "Min. height" is taken over a later
+/// "Max. height". Numbers are read with the separators of `country`, and
+/// units it spells its own way are translated first.
+pub fn parse_dimensions(text: &str, country: &Country) -> Dimensions {
+    let mut dimensions = Dimensions::default();
+
+    for line in text.lines() {
+        let (label, value) = match line.split_once(':') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let label = label.trim().to_lowercase();
+        let labels = &country.dimension_labels;
+
+        let (slot, factor): (&mut Option<f64>, Factor) = if is_one_of(&label, &labels.width) {
+            (&mut dimensions.width, centimetres)
+        } else if is_one_of(&label, &labels.depth) {
+            (&mut dimensions.depth, centimetres)
+        } else if is_one_of(&label, &labels.height) {
+            (&mut dimensions.height, centimetres)
+        } else if is_one_of(&label, &labels.length) {
+            (&mut dimensions.length, centimetres)
+        } else if is_one_of(&label, &labels.weight) {
+            (&mut dimensions.weight, kilograms)
+        } else if is_one_of(&label, &labels.volume) {
+            (&mut dimensions.volume, litres)
+        } else {
+            continue;
+        };
+
+        if slot.is_some() {
+            continue;
+        }
+
+        if let Some((amount, unit)) = parse_measure(value, country) {
+            let unit = country.dimension_units.get(&unit).unwrap_or(&unit);
+            *slot = factor(unit).map(|factor| (amount * factor * 100.0).round() / 100.0);
+        }
+    }
+
+    dimensions
+}
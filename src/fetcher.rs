@@ -0,0 +1,113 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::result;
+
+// Hyper
+use hyper;
+use hyper::client::Client;
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+
+pub type Result<T> = result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be completed.
+    Http(hyper::Error),
+    /// The server answered with a non-success status.
+    Status(StatusCode),
+    /// The page is not stored locally and may not be fetched.
+    Missing(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Http(ref error) => write!(f, "{}", error),
+            Error::Status(status) => write!(f, "unexpected status {}", status),
+            Error::Missing(ref url) => write!(f, "no stored copy of {}", url),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<hyper::Error> for Error {
+    fn from(error: hyper::Error) -> Error {
+        Error::Http(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Http(hyper::Error::Io(error))
+    }
+}
+
+/// A fetched page: the response status, headers and raw body.
+#[derive(Clone)]
+pub struct Page {
+    pub status: StatusCode,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+impl Page {
+    /// Returns the body decoded as UTF-8, replacing invalid sequences.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// Transport used by the spider to fetch pages.
+///
+/// The crawl functions only ever talk to a `Fetcher`, so caching,
+/// rate-limited or fixture-backed implementations can be swapped in
+/// without touching the crawl logic. Fetchers are shared between the
+/// product page workers, so they must be `Sync`.
+pub trait Fetcher: Sync {
+    /// Fetches `url`, sending `headers` along with the request.
+    fn fetch(&self, url: &str, headers: &Headers) -> Result<Page>;
+}
+
+impl<F: Fetcher + ?Sized> Fetcher for Box<F> {
+    fn fetch(&self, url: &str, headers: &Headers) -> Result<Page> {
+        (**self).fetch(url, headers)
+    }
+}
+
+/// Fetches pages over the network with a shared hyper client.
+pub struct HyperFetcher {
+    client: Client,
+}
+
+impl HyperFetcher {
+    pub fn new() -> HyperFetcher {
+        HyperFetcher {
+            client: Client::new(),
+        }
+    }
+}
+
+impl Default for HyperFetcher {
+    fn default() -> HyperFetcher {
+        HyperFetcher::new()
+    }
+}
+
+impl Fetcher for HyperFetcher {
+    fn fetch(&self, url: &str, headers: &Headers) -> Result<Page> {
+        let mut res = self.client.get(url).headers(headers.clone()).send()?;
+
+        let mut body = Vec::new();
+        res.read_to_end(&mut body)?;
+
+        Ok(Page {
+            status: res.status,
+            headers: res.headers.clone(),
+            body,
+        })
+    }
+}
@@ -0,0 +1,1082 @@
+extern crate hyper;
+extern crate kuchiki;
+extern crate postgres;
+extern crate url;
+extern crate undup;
+extern crate rand;
+extern crate time;
+extern crate sha2;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate toml;
+
+pub mod cache;
+pub mod compare;
+pub mod country;
+pub mod csv;
+pub mod dimensions;
+pub mod fetcher;
+pub mod mirror;
+pub mod price;
+pub mod ratelimit;
+pub mod replay;
+pub mod retry;
+pub mod selectors;
+pub mod structured_data;
+
+// Std
+use std::fs::File;
+use std::io::prelude::*;
+use std::string::String;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+// Hyper
+use hyper::header::Headers;
+
+// Country
+pub use country::Country;
+
+// Dimensions
+use dimensions::{Dimensions, Package};
+
+// Fetcher
+use fetcher::Fetcher;
+
+// Mirror
+use mirror::Mirror;
+
+// Selectors
+use selectors::Profile;
+
+// Kuchiki
+use kuchiki::traits::*;
+use kuchiki::NodeRef;
+
+// Postgres
+use postgres::Connection;
+
+// URL
+use url::Url;
+
+pub const BASE_ADDRESS: &str = "http://www.ikea.com";
+
+#[derive(Default)]
+pub struct Product {
+    pub id: String,
+    pub name: String,
+    pub typ: String,
+    pub country: String,
+    pub unit: String,
+    pub price: String,
+    pub price_amount: Option<f64>,
+    pub price_currency: String,
+    pub regular_price: Option<f64>,
+    pub discounted_price: Option<f64>,
+    pub family_price: Option<f64>,
+    pub offer_valid_from: String,
+    pub offer_valid_to: String,
+    pub metric: String,
+    pub dimensions: Dimensions,
+    pub image_url: String,
+    pub images: Vec<Image>,
+    pub url: String,
+    pub variant_urls: Vec<String>,
+    pub parent_id: String,
+    pub parent_url: String,
+    pub buy_online: Option<bool>,
+    pub stock_hint: String,
+    pub is_new: bool,
+    pub last_chance: bool,
+    pub description: String,
+    pub key_features: Vec<String>,
+    pub materials: String,
+    pub care_instructions: String,
+    pub designer: String,
+    pub good_to_know: String,
+    pub documents: Vec<Document>,
+    pub packages: Vec<Package>,
+    pub rating: Option<f64>,
+    pub review_count: Option<u32>,
+	pub department: String,
+	pub category: String,
+	pub subcategory: String,
+	pub department_url: String,
+	pub category_url: String,
+	pub subcategory_url: String,
+}
+
+/// One picture of a product's gallery, with its larger renditions where the
+/// page offers them. `path` and `hash` are filled in once the picture has
+/// been saved to an image mirror.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Image {
+    pub url: String,
+    pub large: String,
+    pub zoom: String,
+    pub path: String,
+    pub hash: String,
+}
+
+/// A PDF linked from a product page: assembly instructions, a manual or
+/// product information. `path` and `hash` are filled in once the file has
+/// been saved to a document mirror.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Document {
+    pub url: String,
+    pub kind: String,
+    pub language: String,
+    pub path: String,
+    pub hash: String,
+}
+
+#[derive(Clone)]
+pub struct Department {
+    pub name: String,
+    pub url: String,
+}
+
+/// The site being crawled and the fetcher used to reach it. The images and
+/// documents of every product fetched are saved into `image_mirror` and
+/// `document_mirror` when they are set.
+pub struct Site<'a> {
+    pub fetcher: &'a dyn Fetcher,
+    pub base_url: Url,
+    pub image_mirror: Option<&'a Mirror>,
+    pub document_mirror: Option<&'a Mirror>,
+    /// Where fields are found on the pages of the country being crawled.
+    pub selectors: Profile,
+}
+
+impl<'a> Site<'a> {
+    pub fn new(fetcher: &'a dyn Fetcher, base_url: Url) -> Site<'a> {
+        Site {
+            fetcher,
+            base_url,
+            image_mirror: None,
+            document_mirror: None,
+            selectors: Profile::default(),
+        }
+    }
+
+    /// Path under which the site sits on its host, ending in `/`. Paths
+    /// on the site, such as country paths and stored product URLs, are
+    /// relative to it, so a mirror may be crawled at `http://host/mirror/`.
+    fn prefix(&self) -> String {
+        let path = self.base_url.path();
+        if path.ends_with('/') { path.to_string() } else { format!("{}/", path) }
+    }
+
+    /// Absolute address of `url`, which is either a path on the site or
+    /// already absolute.
+    pub fn address(&self, url: &str) -> String {
+        let url = if url.starts_with('/') && !url.starts_with("//") {
+            format!("{}{}", self.prefix(), &url[1..])
+        } else {
+            url.to_string()
+        };
+
+        match self.base_url.join(&url) {
+            Ok(address) => address.into_string(),
+            Err(_) => url,
+        }
+    }
+
+    /// Resolves an absolute, protocol-relative or relative `href` found on
+    /// the page at `page_address`. Links on the crawled site are kept as a
+    /// path on it so stored URLs do not depend on where the site was
+    /// crawled; links elsewhere are kept absolute. Links that are not
+    /// http or https, such as `javascript:` or `mailto:`, are `None`.
+    pub fn link(&self, page_address: &str, href: &str) -> Option<String> {
+        let url = Url::parse(page_address).ok()?.join(href).ok()?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return None;
+        }
+
+        let prefix = self.prefix();
+        if url.scheme() != self.base_url.scheme()
+            || url.host_str() != self.base_url.host_str()
+            || url.port_or_known_default() != self.base_url.port_or_known_default()
+            || !url.path().starts_with(&prefix) {
+            return Some(url.into_string());
+        }
+
+        let mut path = format!("/{}", &url.path()[prefix.len()..]);
+        if let Some(query) = url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+        Some(path)
+    }
+}
+
+pub enum Output {
+    File(String),
+    Database(Box<Connection>),
+}
+
+fn fetch_html(fetcher: &dyn Fetcher, url: &str) -> fetcher::Result<NodeRef> {
+    let page = fetcher.fetch(url, &Headers::new())?;
+    if !page.status.is_success() {
+        return Err(fetcher::Error::Status(page.status));
+    }
+
+    Ok(kuchiki::parse_html().one(page.text()))
+}
+
+pub fn write_department_products(site: &Site, country: &Country, output: Output, concurrency: usize, error_str: &mut String) {
+    let mut m = BTreeMap::<String, Product>::new();
+    let mut visited_urls = BTreeMap::<String, bool>::new();
+
+    let departments = match fetch_departments(site, country) {
+        Some(departments) => departments,
+        None => return,
+    };
+
+    for department in departments {
+        fetch_products_from_all_departments(site, &mut visited_urls, &mut m, vec![department], error_str);
+
+        match output {
+            Output::File(ref filename) => write_to_file(site, &m, filename, country, concurrency, error_str),
+            Output::Database(ref conn) => write_to_database(site, &m, conn, country, concurrency, error_str),
+        }
+    }
+}
+
+/// Fetches the product page of every entry in `m` and hands the results to
+/// `write` in the order of `m`, together with the listing entry, a running
+/// count and the number of pages known so far, listed or variants. Variants
+/// linked from those pages that are not listed themselves are fetched in
+/// further passes, under the listing of the page that linked them, until no
+/// new ones turn up.
+fn fetch_products<W>(site: &Site, m: &BTreeMap<String, Product>, country: &Country, concurrency: usize, error_str: &mut String, mut write: W)
+    where W: FnMut(&Product, Product, usize, usize)
+{
+    let mut seen: BTreeSet<String> = m.keys().cloned().collect();
+    let mut variants: Option<BTreeMap<String, Product>> = None;
+    let mut count = 0;
+    let mut total = m.len();
+
+    loop {
+        let entries: Vec<(&String, &Product)> = match variants {
+            Some(ref variants) => variants.iter().collect(),
+            None => m.iter().collect(),
+        };
+
+        let mut found = BTreeMap::new();
+        fetch_batch(site, &entries, country, concurrency, error_str, |listing, mut product| {
+            // A badge may show on the product list but not the product page.
+            product.is_new |= listing.is_new;
+            product.last_chance |= listing.last_chance;
+
+            for url in &product.variant_urls {
+                if seen.insert(url.clone()) {
+                    found.insert(url.clone(), variant_listing(listing, &product, url));
+                    total += 1;
+                }
+            }
+
+            count += 1;
+            write(listing, product, count, total);
+        });
+
+        if found.is_empty() {
+            break;
+        }
+        variants = Some(found);
+    }
+}
+
+/// Listing entry for a variant found on the page of `parent`, filed where
+/// the parent was listed.
+fn variant_listing(listing: &Product, parent: &Product, url: &str) -> Product {
+    Product {
+        url: url.to_string(),
+        parent_id: parent.id.clone(),
+        parent_url: parent.url.clone(),
+        department: listing.department.clone(),
+        category: listing.category.clone(),
+        subcategory: listing.subcategory.clone(),
+        department_url: listing.department_url.clone(),
+        category_url: listing.category_url.clone(),
+        subcategory_url: listing.subcategory_url.clone(),
+        ..Product::default()
+    }
+}
+
+/// Fetches the product page of every entry on a pool of `concurrency`
+/// workers and hands the results to `handle` in the order of `entries`.
+fn fetch_batch<H>(site: &Site, entries: &[(&String, &Product)], country: &Country, concurrency: usize, error_str: &mut String, mut handle: H)
+    where H: FnMut(&Product, Product)
+{
+    let next_entry = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..concurrency.max(1) {
+            let tx = tx.clone();
+            let next_entry = &next_entry;
+
+            scope.spawn(move || loop {
+                let n = next_entry.fetch_add(1, Ordering::SeqCst);
+                if n >= entries.len() {
+                    break;
+                }
+
+                let mut errors = String::new();
+                let mut product = fetch_product_info(site, entries[n].0.as_str(), country, &mut errors);
+                if let Some(product) = product.as_mut() {
+                    if let Some(mirror) = site.image_mirror {
+                        mirror.download_images(site, country, product, &mut errors);
+                    }
+                    if let Some(mirror) = site.document_mirror {
+                        mirror.download_documents(site, country, product, &mut errors);
+                    }
+                }
+                if tx.send((n, product, errors)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        // Workers finish out of order, so hold results back until every
+        // earlier entry has been handled.
+        let mut pending = BTreeMap::new();
+        let mut next_handle = 0;
+        for (n, product, errors) in rx {
+            pending.insert(n, (product, errors));
+
+            while let Some((product, errors)) = pending.remove(&next_handle) {
+                error_str.push_str(&errors);
+                if let Some(product) = product {
+                    handle(entries[next_handle].1, product);
+                }
+                next_handle += 1;
+            }
+        }
+    });
+}
+
+fn write_to_file(site: &Site, m: &BTreeMap<String, Product>, output: &str, country: &Country, concurrency: usize, error_str: &mut String) {
+    let mut f = match File::create(output) {
+        Ok(file) => file,
+        Err(error) => panic!("{}", error),
+    };
+
+    if let Err(error) = f.write_all(b"Item Number,Name,Type,Price,Unit,Metric,Image URL,URL,Department,Category,Subcategory,Department URL,Category URL, Subcategory URL,Price Amount,Price Currency,Regular Price,Discounted Price,Family Price,Offer Valid From,Offer Valid To,Width (cm),Depth (cm),Height (cm),Length (cm),Weight (kg),Volume (l),Images,Parent Item Number,Parent URL,Variant URLs,Buy Online,Stock,New,Last Chance,Description,Key Features,Materials,Care Instructions,Designer,Good to Know,Documents,Packages,Rating,Review Count\n") {
+        panic!("{}", error);
+    }
+
+    fetch_products(site, m, country, concurrency, error_str, |listing, product, index, total| {
+        let amount = |amount: Option<f64>| amount.map(|amount| format!("{:.2}", amount)).unwrap_or_default();
+        let price_amount = amount(product.price_amount);
+        let regular_price = amount(product.regular_price);
+        let discounted_price = amount(product.discounted_price);
+        let family_price = amount(product.family_price);
+        let measure = |measure: Option<f64>| measure.map(|measure| measure.to_string()).unwrap_or_default();
+        let width = measure(product.dimensions.width);
+        let depth = measure(product.dimensions.depth);
+        let height = measure(product.dimensions.height);
+        let length = measure(product.dimensions.length);
+        let weight = measure(product.dimensions.weight);
+        let volume = measure(product.dimensions.volume);
+        let images = serde_json::to_string(&product.images).unwrap_or_default();
+        let variant_urls = serde_json::to_string(&product.variant_urls).unwrap_or_default();
+        let buy_online = product.buy_online.map(|buy_online| buy_online.to_string()).unwrap_or_default();
+        let is_new = product.is_new.to_string();
+        let last_chance = product.last_chance.to_string();
+        let key_features = serde_json::to_string(&product.key_features).unwrap_or_default();
+        let documents = serde_json::to_string(&product.documents).unwrap_or_default();
+        let packages = serde_json::to_string(&product.packages).unwrap_or_default();
+        let rating = product.rating.map(|rating| rating.to_string()).unwrap_or_default();
+        let review_count = product.review_count.map(|count| count.to_string()).unwrap_or_default();
+
+        if let Err(error) = f.write_all(csv::format_record(&[
+                 &product.id,
+                 &product.name,
+                 &product.typ,
+                 &product.price,
+                 &product.unit,
+                 &product.metric,
+                 &product.image_url,
+                 &product.url,
+                 &listing.department,
+                 &listing.category,
+                 &listing.subcategory,
+                 &listing.department_url,
+                 &listing.category_url,
+                 &listing.subcategory_url,
+                 &price_amount,
+                 &product.price_currency,
+                 &regular_price,
+                 &discounted_price,
+                 &family_price,
+                 &product.offer_valid_from,
+                 &product.offer_valid_to,
+                 &width,
+                 &depth,
+                 &height,
+                 &length,
+                 &weight,
+                 &volume,
+                 &images,
+                 &listing.parent_id,
+                 &listing.parent_url,
+                 &variant_urls,
+                 &buy_online,
+                 &product.stock_hint,
+                 &is_new,
+                 &last_chance,
+                 &product.description,
+                 &key_features,
+                 &product.materials,
+                 &product.care_instructions,
+                 &product.designer,
+                 &product.good_to_know,
+                 &documents,
+                 &packages,
+                 &rating,
+                 &review_count,
+				]).as_bytes()) {
+
+            panic!("{}", error);
+
+        }
+
+        println!("{}: {}: {}: {} ({}/{})", &listing.department, &listing.category, &listing.subcategory, product.name, index, total);
+    });
+}
+
+fn write_to_database(site: &Site, m: &BTreeMap<String, Product>, conn: &Connection, country: &Country, concurrency: usize, error_str: &mut String) {
+    fetch_products(site, m, country, concurrency, error_str, |listing, product, index, total| {
+        let review_count = product.review_count.map(|count| count as i32);
+
+        conn.execute("INSERT INTO product (
+                          id,
+                          name,
+                          type,
+                          country,
+                          price,
+                          unit,
+                          metric,
+                          url,
+                          image_url,
+                          department,
+                          category,
+                          subcategory,
+                          department_url,
+                          category_url,
+                          subcategory_url,
+                          price_amount,
+                          price_currency,
+                          regular_price,
+                          discounted_price,
+                          family_price,
+                          offer_valid_from,
+                          offer_valid_to,
+                          width_cm,
+                          depth_cm,
+                          height_cm,
+                          length_cm,
+                          weight_kg,
+                          volume_l,
+                          parent_id,
+                          parent_url,
+                          buy_online,
+                          stock_hint,
+                          is_new,
+                          last_chance,
+                          description,
+                          key_features,
+                          materials,
+                          care_instructions,
+                          designer,
+                          good_to_know,
+                          rating,
+                          review_count,
+                          created_at,
+                          updated_at
+                      ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16::FLOAT8, $17,
+                                $18::FLOAT8, $19::FLOAT8, $20::FLOAT8, NULLIF($21::TEXT, '')::DATE, NULLIF($22::TEXT, '')::DATE,
+                                $23::FLOAT8, $24::FLOAT8, $25::FLOAT8, $26::FLOAT8, $27::FLOAT8, $28::FLOAT8, $29, $30,
+                                $31, $32, $33, $34, $35, $36, $37, $38, $39, $40,
+                                $41::FLOAT8, $42, NOW(), NOW())
+                        ON CONFLICT (id, country, url)
+                        DO UPDATE SET
+                            name=$2,
+                            type=$3,
+                            country=$4,
+                            price=$5,
+                            unit=$6,
+                            metric=$7,
+                            url=$8,
+                            image_url=$9,
+                            department=$10,
+                            category=$11,
+                            subcategory=$12,
+                            department_url=$13,
+                            category_url=$14,
+                            subcategory_url=$15,
+                            price_amount=$16::FLOAT8,
+                            price_currency=$17,
+                            regular_price=$18::FLOAT8,
+                            discounted_price=$19::FLOAT8,
+                            family_price=$20::FLOAT8,
+                            offer_valid_from=NULLIF($21::TEXT, '')::DATE,
+                            offer_valid_to=NULLIF($22::TEXT, '')::DATE,
+                            width_cm=$23::FLOAT8,
+                            depth_cm=$24::FLOAT8,
+                            height_cm=$25::FLOAT8,
+                            length_cm=$26::FLOAT8,
+                            weight_kg=$27::FLOAT8,
+                            volume_l=$28::FLOAT8,
+                            parent_id=$29,
+                            parent_url=$30,
+                            buy_online=$31,
+                            stock_hint=$32,
+                            is_new=$33,
+                            last_chance=$34,
+                            description=$35,
+                            key_features=$36,
+                            materials=$37,
+                            care_instructions=$38,
+                            designer=$39,
+                            good_to_know=$40,
+                            rating=$41::FLOAT8,
+                            review_count=$42,
+                            updated_at=NOW()",
+                         &[
+                            &product.id,
+                            &product.name,
+                            &product.typ,
+                            &product.country,
+                            &product.price,
+                            &product.unit,
+                            &product.metric,
+                            &product.url,
+                            &product.image_url,
+                            &listing.department,
+                            &listing.category,
+                            &listing.subcategory,
+                            &listing.department_url,
+                            &listing.category_url,
+                            &listing.subcategory_url,
+                            &product.price_amount,
+                            &product.price_currency,
+                            &product.regular_price,
+                            &product.discounted_price,
+                            &product.family_price,
+                            &product.offer_valid_from,
+                            &product.offer_valid_to,
+                            &product.dimensions.width,
+                            &product.dimensions.depth,
+                            &product.dimensions.height,
+                            &product.dimensions.length,
+                            &product.dimensions.weight,
+                            &product.dimensions.volume,
+                            &listing.parent_id,
+                            &listing.parent_url,
+                            &product.buy_online,
+                            &product.stock_hint,
+                            &product.is_new,
+                            &product.last_chance,
+                            &product.description,
+                            &product.key_features,
+                            &product.materials,
+                            &product.care_instructions,
+                            &product.designer,
+                            &product.good_to_know,
+                            &product.rating,
+                            &review_count,
+                         ]).unwrap();
+
+        // Only changes are recorded, so each row marks when a product came
+        // into or went out of stock.
+        conn.execute("INSERT INTO product_availability (
+                          product_id,
+                          country,
+                          product_url,
+                          buy_online,
+                          stock_hint,
+                          checked_at
+                      ) SELECT $1::VARCHAR, $2::VARCHAR, $3::VARCHAR, $4::BOOLEAN, $5::VARCHAR, NOW()
+                        WHERE NOT EXISTS (
+                            SELECT 1 FROM (
+                                SELECT buy_online, stock_hint FROM product_availability
+                                 WHERE product_id=$1 AND country=$2 AND product_url=$3
+                                 ORDER BY checked_at DESC
+                                 LIMIT 1
+                            ) latest
+                            WHERE latest.buy_online IS NOT DISTINCT FROM $4 AND latest.stock_hint=$5
+                        )",
+                     &[&product.id, &product.country, &product.url, &product.buy_online, &product.stock_hint]).unwrap();
+
+        // Ratings are recorded with the price they were seen at, again only
+        // when one of them changes.
+        if product.rating.is_some() || product.review_count.is_some() {
+            conn.execute("INSERT INTO product_rating (
+                              product_id,
+                              country,
+                              product_url,
+                              rating,
+                              review_count,
+                              price_amount,
+                              checked_at
+                          ) SELECT $1::VARCHAR, $2::VARCHAR, $3::VARCHAR, $4::FLOAT8, $5::INTEGER, $6::FLOAT8, NOW()
+                            WHERE NOT EXISTS (
+                                SELECT 1 FROM (
+                                    SELECT rating, review_count, price_amount FROM product_rating
+                                     WHERE product_id=$1 AND country=$2 AND product_url=$3
+                                     ORDER BY checked_at DESC
+                                     LIMIT 1
+                                ) latest
+                                WHERE latest.rating IS NOT DISTINCT FROM $4::FLOAT8::NUMERIC(3, 2)
+                                  AND latest.review_count IS NOT DISTINCT FROM $5
+                                  AND latest.price_amount IS NOT DISTINCT FROM $6::FLOAT8::NUMERIC(12, 2)
+                            )",
+                         &[&product.id, &product.country, &product.url, &product.rating, &review_count, &product.price_amount]).unwrap();
+        }
+
+        conn.execute("DELETE FROM product_image WHERE product_id=$1 AND country=$2 AND product_url=$3",
+                     &[&product.id, &product.country, &product.url]).unwrap();
+        for (position, image) in product.images.iter().enumerate() {
+            conn.execute("INSERT INTO product_image (
+                              product_id,
+                              country,
+                              product_url,
+                              position,
+                              url,
+                              large_url,
+                              zoom_url,
+                              local_path,
+                              sha256
+                          ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                         &[
+                            &product.id,
+                            &product.country,
+                            &product.url,
+                            &(position as i32),
+                            &image.url,
+                            &image.large,
+                            &image.zoom,
+                            &image.path,
+                            &image.hash,
+                         ]).unwrap();
+        }
+
+        conn.execute("DELETE FROM product_document WHERE product_id=$1 AND country=$2 AND product_url=$3",
+                     &[&product.id, &product.country, &product.url]).unwrap();
+        for (position, document) in product.documents.iter().enumerate() {
+            conn.execute("INSERT INTO product_document (
+                              product_id,
+                              country,
+                              product_url,
+                              position,
+                              url,
+                              kind,
+                              language,
+                              local_path,
+                              sha256
+                          ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                         &[
+                            &product.id,
+                            &product.country,
+                            &product.url,
+                            &(position as i32),
+                            &document.url,
+                            &document.kind,
+                            &document.language,
+                            &document.path,
+                            &document.hash,
+                         ]).unwrap();
+        }
+
+        conn.execute("DELETE FROM product_package WHERE product_id=$1 AND country=$2 AND product_url=$3",
+                     &[&product.id, &product.country, &product.url]).unwrap();
+        for (position, package) in product.packages.iter().enumerate() {
+            conn.execute("INSERT INTO product_package (
+                              product_id,
+                              country,
+                              product_url,
+                              position,
+                              item_number,
+                              quantity,
+                              width_cm,
+                              height_cm,
+                              length_cm,
+                              weight_kg
+                          ) VALUES ($1, $2, $3, $4, $5, $6, $7::FLOAT8, $8::FLOAT8, $9::FLOAT8, $10::FLOAT8)",
+                         &[
+                            &product.id,
+                            &product.country,
+                            &product.url,
+                            &(position as i32),
+                            &package.item_number,
+                            &(package.quantity as i32),
+                            &package.dimensions.width,
+                            &package.dimensions.height,
+                            &package.dimensions.length,
+                            &package.dimensions.weight,
+                         ]).unwrap();
+        }
+
+        conn.execute("DELETE FROM product_variant WHERE product_id=$1 AND country=$2 AND product_url=$3",
+                     &[&product.id, &product.country, &product.url]).unwrap();
+        for variant_url in &product.variant_urls {
+            conn.execute("INSERT INTO product_variant (
+                              product_id,
+                              country,
+                              product_url,
+                              variant_url
+                          ) VALUES ($1, $2, $3, $4)",
+                         &[
+                            &product.id,
+                            &product.country,
+                            &product.url,
+                            variant_url,
+                         ]).unwrap();
+        }
+        println!("{}: {}: {}: {} ({}/{})", &listing.department, &listing.category, &listing.subcategory, product.name, index, total);
+    });
+}
+
+pub fn fetch_departments(site: &Site, country: &Country) -> Option<Vec<Department>> {
+    let address = &site.address(&country.path);
+    let document = &match fetch_html(site.fetcher, address) {
+        Ok(doc) => doc,
+        Err(error) => {
+            println!("error: fetch_departments: {:?}", error);
+            return None;
+        }
+    };
+
+    let mut departments = Vec::new();
+
+    for node in site.selectors.select(document, "departments") {
+        let url = match site.selectors.read(&node, "link") {
+            None => continue,
+            Some(url) => url,
+        };
+
+        if url == "#" {
+            continue;
+        }
+
+        let url = match site.link(address, &url) {
+            Some(url) => url,
+            None => continue,
+        };
+
+        let name = match site.selectors.field("departments").and_then(|field| field.read(&node)) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        departments.push(Department{
+            url,
+            name,
+        });
+    }
+
+    Some(departments)
+}
+
+pub fn fetch_products_from_all_departments(site: &Site, visited_urls: &mut BTreeMap<String, bool>, m: &mut BTreeMap<String, Product>, hierarchy: Vec<Department>, error_str: &mut String) {
+    let department = if let Some(department) = hierarchy.last() {
+        department
+    } else {
+        return;
+    };
+
+    let address = &site.address(&department.url);
+    let document = &match fetch_html(site.fetcher, address) {
+        Ok(doc) => doc,
+        Err(error) => {
+            println!("error: fetch_products_from_all_departments: {:?}", error);
+            error_str.push_str(&format!("Failed to fetch HTML at {}\n", address));
+            return;
+        }
+    };
+
+    if site.selectors.has(document, "product_list") {
+        for node in site.selectors.select(document, "product_links") {
+            let url = match site.selectors.read(&node, "link") {
+                None => continue,
+                Some(url) => url,
+            };
+
+            if url == "#" {
+                continue;
+            }
+
+            let url = match site.link(address, &url) {
+                Some(url) => url,
+                None => continue,
+            };
+
+            println!("PRODUCT URL {}", url);
+
+            // Badges sit beside the link, in the tile of the product.
+            let product_node = node.ancestors()
+                .find(|ancestor| site.selectors.is(ancestor, "product_tile"))
+                .unwrap_or_else(|| node.clone());
+
+            let product = Product {
+                url: url.clone(),
+                is_new: site.selectors.has(&product_node, "new_badge"),
+                last_chance: site.selectors.has(&product_node, "last_chance_badge"),
+                department: hierarchy[0].name.clone(),
+                category: if hierarchy.len() >= 2 { hierarchy[1].name.clone() } else { "".to_string() },
+                subcategory: if hierarchy.len() >= 3 { hierarchy[2].name.clone() } else { "".to_string() },
+                department_url: hierarchy[0].url.clone(),
+                category_url: if hierarchy.len() >= 2 { hierarchy[1].url.clone() } else { "".to_string() },
+                subcategory_url: if hierarchy.len() >= 3 { hierarchy[2].url.clone() } else { "".to_string() },
+                ..Product::default()
+            };
+
+            m.insert(url.clone(), product);
+        }
+    } else {
+        for node in site.selectors.select(document, "categories") {
+            let mut department = Department {
+                url: "".to_string(),
+                name: "".to_string(),
+            };
+            department.url = match site.selectors.read(&node, "link").and_then(|url| site.link(address, &url)) {
+                None => continue,
+                Some(url) => url,
+            };
+
+
+            let text = if let Some(text) = node.parent().and_then(|parent| site.selectors.value(&parent, "category_name")) {
+                text
+            } else {
+                continue;
+            };
+
+            department.name = text.clone();
+
+            if visited_urls.contains_key(&department.url) {
+                continue;
+            }
+            visited_urls.insert(department.url.clone(), true);
+
+            let mut next_hierarchy = hierarchy.clone();
+            next_hierarchy.push(department);
+
+            fetch_products_from_all_departments(site, visited_urls, m, next_hierarchy, error_str);
+        }
+    }
+}
+
+pub fn fetch_product_info(site: &Site, url: &str, country: &Country, error_str: &mut String) -> Option<Product> {
+    let address = site.address(url);
+    let document = match fetch_html(site.fetcher, &address) {
+        Ok(doc) => doc,
+        Err(error) => {
+            error_str.push_str(&format!("Failed to fetch product data at {}\n", &address));
+            println!("error: fetch_product_info: {}", error);
+            return None;
+        }
+    };
+
+    // schema.org data survives layout changes, so where the page embeds it
+    // it is preferred to the selectors below.
+    let structured = structured_data::extract(&document);
+
+    let selectors = &site.selectors;
+
+    let price = selectors.value(&document, "price").unwrap_or_default();
+    let price_amount = structured.price.or_else(|| price::parse_price(&price, country).map(|parsed| parsed.amount));
+    let price_currency = match price_amount {
+        Some(_) => structured.currency.clone().unwrap_or_else(|| country.currency.clone()),
+        None => String::new(),
+    };
+
+    let measurements = selectors.value(&document, "measures")
+        .or_else(|| selectors.value(&document, "metric"))
+        .unwrap_or_default();
+
+    // While an offer runs, the price field holds the discounted price and
+    // the previous price the one it replaced.
+    let previous = selectors.value(&document, "previous_price").and_then(|previous| price::parse_price(&previous, country));
+    let (regular_price, discounted_price) = match previous {
+        Some(previous) => (Some(previous.amount), price_amount),
+        None => (price_amount, None),
+    };
+
+    let mut images = fetch_images(selectors, &document);
+    if images.is_empty() {
+        images = structured.images.iter()
+            .filter_map(|image| site.link(&address, image))
+            .map(|url| Image { url, large: String::new(), zoom: String::new(), path: String::new(), hash: String::new() })
+            .collect();
+    }
+
+    Some(Product {
+        id: structured.sku.as_ref().map(|sku| sku.replace(".", ""))
+            .or_else(|| selectors.value(&document, "item_number"))
+            .unwrap_or_default(),
+        name: structured.name.clone().or_else(|| selectors.value(&document, "name")).unwrap_or_default(),
+        typ: selectors.value(&document, "type").unwrap_or_default(),
+        price,
+        price_amount,
+        price_currency,
+        regular_price,
+        discounted_price,
+        family_price: selectors.value(&document, "family_price").and_then(|family| price::parse_price(&family, country)).map(|family| family.amount),
+        offer_valid_from: selectors.value(&document, "valid_from").and_then(|date| price::parse_date(&date)).unwrap_or_default(),
+        offer_valid_to: selectors.value(&document, "valid_to").and_then(|date| price::parse_date(&date)).unwrap_or_default(),
+        country: country.name.clone(),
+        unit: selectors.value(&document, "unit").unwrap_or_default(),
+        metric: selectors.value(&document, "metric").unwrap_or_default(),
+        dimensions: dimensions::parse_dimensions(&measurements, country),
+        image_url: structured.images.first().and_then(|image| site.link(&address, image))
+            .or_else(|| selectors.value(&document, "image"))
+            .unwrap_or_default(),
+        images,
+        url: String::from(url),
+        variant_urls: fetch_variant_urls(site, &document, &address, url),
+        parent_id: "".to_string(),
+        parent_url: "".to_string(),
+        // The page says whether the product sells online; schema.org only
+        // whether it is in stock somewhere.
+        buy_online: if selectors.has(&document, "not_available_online") {
+            Some(false)
+        } else if selectors.has(&document, "buy_online_button") {
+            Some(true)
+        } else {
+            structured.availability
+        },
+        stock_hint: selectors.value(&document, "stock").unwrap_or_default(),
+        is_new: selectors.has(&document, "new_badge"),
+        last_chance: selectors.has(&document, "last_chance_badge"),
+        description: selectors.value(&document, "description").unwrap_or_default(),
+        key_features: selectors.values(&document, "key_features"),
+        materials: selectors.value(&document, "materials").unwrap_or_default(),
+        care_instructions: selectors.value(&document, "care_instructions").unwrap_or_default(),
+        designer: selectors.values(&document, "designer").join(", "),
+        good_to_know: selectors.value(&document, "good_to_know").unwrap_or_default(),
+        documents: fetch_documents(site, &document, &address, country),
+        packages: fetch_packages(selectors, &document, country),
+        rating: selectors.value(&document, "rating").and_then(|rating| price::parse_amount(&rating, country.decimal, country.grouping)),
+        review_count: selectors.value(&document, "review_count").and_then(|count| count.parse().ok()),
+		department: "".to_string(),
+		category: "".to_string(),
+		subcategory: "".to_string(),
+		department_url: "".to_string(),
+		category_url: "".to_string(),
+		subcategory_url: "".to_string(),
+    })
+}
+
+/// Returns the pages of the other colours, sizes and finishes offered on a
+/// product page, resolved like category links, without the page itself.
+fn fetch_variant_urls(site: &Site, document: &NodeRef, address: &str, url: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+
+    for node in site.selectors.select(document, "variants") {
+        let href = match site.selectors.read(&node, "link") {
+            Some(href) if href != "#" => href,
+            _ => continue,
+        };
+
+        if let Some(variant_url) = site.link(address, &href) {
+            if variant_url != url && !urls.contains(&variant_url) {
+                urls.push(variant_url);
+            }
+        }
+    }
+
+    urls
+}
+
+/// Returns the packages listed in the package section of a product page,
+/// each with its article number, how many of it there are and its
+/// measurements.
+fn fetch_packages(selectors: &Profile, document: &NodeRef, country: &Country) -> Vec<Package> {
+    selectors.select(document, "packages").iter().map(|node| {
+        let item_number = selectors.value(node, "package_item_number").unwrap_or_default();
+
+        let quantity = selectors.value(node, "package_quantity")
+            .and_then(|quantity| quantity.parse().ok())
+            .unwrap_or(1);
+
+        let measures = selectors.value(node, "package_measures").unwrap_or_default();
+
+        Package {
+            item_number,
+            quantity,
+            dimensions: dimensions::parse_dimensions(&measures, country),
+        }
+    }).collect()
+}
+
+/// Returns the PDFs linked from the document sections of a product page.
+/// The `document_language` of a link gives its language, which otherwise is
+/// the site's.
+fn fetch_documents(site: &Site, document: &NodeRef, address: &str, country: &Country) -> Vec<Document> {
+    let sections = [
+        ("assembly_instructions", "assembly"),
+        ("manuals", "manual"),
+        ("product_information", "product-info"),
+    ];
+
+    let mut documents: Vec<Document> = Vec::new();
+    for &(section, kind) in &sections {
+        for node in site.selectors.select(document, section) {
+            let url = match site.selectors.read(&node, "link").and_then(|href| site.link(address, &href)) {
+                Some(url) => url,
+                None => continue,
+            };
+            if documents.iter().any(|document| document.url == url) {
+                continue;
+            }
+
+            documents.push(Document {
+                url,
+                kind: kind.to_string(),
+                language: site.selectors.read(&node, "document_language").unwrap_or_else(|| country.language.clone()),
+                path: String::new(),
+                hash: String::new(),
+            });
+        }
+    }
+
+    documents
+}
+
+/// Returns the main picture and the gallery thumbnails in page order, each
+/// picture once. Larger renditions are read from `gallery_large` and
+/// `gallery_zoom` on whichever copy of a picture has them.
+fn fetch_images(selectors: &Profile, document: &NodeRef) -> Vec<Image> {
+    let mut images: Vec<Image> = Vec::new();
+
+    for node in selectors.select(document, "gallery") {
+        let url = match selectors.field("gallery").and_then(|field| field.read(&node)) {
+            Some(url) => url,
+            None => continue,
+        };
+        let large = selectors.read(&node, "gallery_large").unwrap_or_default();
+        let zoom = selectors.read(&node, "gallery_zoom").unwrap_or_default();
+
+        match images.iter_mut().find(|image| image.url == url) {
+            Some(image) => {
+                if image.large.is_empty() {
+                    image.large = large;
+                }
+                if image.zoom.is_empty() {
+                    image.zoom = zoom;
+                }
+            },
+            None => images.push(Image { url, large, zoom, path: String::new(), hash: String::new() }),
+        }
+    }
+
+    images
+}
@@ -1,17 +1,15 @@
 extern crate hyper;
-extern crate kuchiki;
 extern crate postgres;
 extern crate url;
 extern crate getopts;
-extern crate undup;
+extern crate ikea_spider_experiment;
 
 // Std
 use std::env;
-use std::fs::File;
-use std::io::prelude::*;
+use std::path::PathBuf;
 use std::string::String;
-use std::collections::BTreeMap;
 use std::result;
+use std::str::FromStr;
 use std::thread::sleep;
 use std::time::{Duration,Instant};
 
@@ -20,467 +18,39 @@ use hyper::client::Client;
 use hyper::client::response::Response;
 use hyper::status::StatusCode;
 
-// Kuchiki
-use kuchiki::traits::*;
-use kuchiki::NodeRef;
-use kuchiki::NodeData::Element;
+// Spider
+use ikea_spider_experiment::{write_department_products, Country, Output, Site, BASE_ADDRESS};
+use ikea_spider_experiment::compare::{load_rates, read_listings, write_comparison};
+use ikea_spider_experiment::country::{default_countries, find_country, load_countries};
+use ikea_spider_experiment::cache::CachingFetcher;
+use ikea_spider_experiment::fetcher::{Fetcher, HyperFetcher};
+use ikea_spider_experiment::mirror::Mirror;
+use ikea_spider_experiment::ratelimit::RateLimitedFetcher;
+use ikea_spider_experiment::replay::{RecordingFetcher, ReplayFetcher};
+use ikea_spider_experiment::retry::{RetryFetcher, RetryPolicy};
+use ikea_spider_experiment::selectors::{default_profiles, load_profiles, Profile};
 
 // Postgres
 use postgres::{Connection, SslMode};
 
 // URL
+use url::Url;
 use url::percent_encoding::*;
 
 // Getopts
 use getopts::{Matches, Options};
 
-// Undup
-use undup::undup_chars;
-
 type Result<T> = result::Result<T, hyper::error::Error>;
 
-const BASE_ADDRESS: &'static str = "http://www.ikea.com";
-
-struct Product {
-    id: String,
-    name: String,
-    typ: String,
-    country: String,
-    unit: String,
-    price: String,
-    metric: String,
-    image_url: String,
-    url: String,
-	department: String,
-	category: String,
-	subcategory: String,
-	department_url: String,
-	category_url: String,
-	subcategory_url: String,
-}
-
-#[derive(Clone)]
-struct Department {
-    name: String,
-    url: String,
-}
-
-struct Country<'a> {
-    name: &'a str,
-    url: &'a str,
-}
-
-enum Output {
-    File(String),
-    Database(Connection),
-}
-
-fn fetch_html(url: &str) -> Result<NodeRef> {
-    let client = Client::new();
-    let mut res = try!(client.get(url).send());
-
-    let mut html = String::new();
-    try!(res.read_to_string(&mut html));
-
-    Ok(kuchiki::parse_html().one(html))
-}
-
-fn write_department_products(country: &Country, output: Output, mut error_str: &mut String) {
-    let mut m = BTreeMap::<String, Product>::new();
-    let mut visited_urls = BTreeMap::<String, bool>::new();
-
-    let departments = match fetch_departments(&country) {
-        Some(departments) => departments,
-        None => return,
-    };
-
-    for department in departments {
-        &fetch_products_from_all_departments(&mut visited_urls, &mut m, vec![department], &mut error_str);
-
-        match output {
-            Output::File(ref filename) => write_to_file(&m, &filename, country, &mut error_str),
-            Output::Database(ref conn) => write_to_database(&m, &conn, country, &mut error_str),
-        }
-    }
-}
-
-fn write_to_file(m: &BTreeMap<String, Product>, output: &str, country: &Country, mut error_str: &mut String) {
-    let max_count = m.len();
-    let mut index = 1;
-
-    let mut f = match File::create(output) {
-        Ok(file) => file,
-        Err(error) => panic!(error),
-    };
-
-    if let Err(error) = f.write_all(b"Item Number,Name,Type,Price,Unit,Metric,Image URL,URL,Department,Category,Subcategory,Department URL,Category URL, Subcategory URL\n") {
-        panic!(error);
-    }
-
-    for i in m {
-        if let Some(product) = fetch_product_info(i.0.as_str(), country, &mut error_str) {
-            if let Err(error) = f.write_all(format!(
-				     "\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\"\n",
-                     product.id,
-                     product.name,
-                     product.typ,
-                     product.price,
-                     product.unit,
-                     product.metric,
-                     product.image_url,
-                     product.url,
-                     &i.1.department,
-                     &i.1.category,
-                     &i.1.subcategory,
-                     &i.1.department_url,
-                     &i.1.category_url,
-                     &i.1.subcategory_url,
-				).as_bytes()) {
-
-                panic!(error);
-
-            }
-
-            println!("{}: {}: {}: {} ({}/{})", &i.1.department, &i.1.category, &i.1.subcategory, product.name, index, max_count);
-            index += 1;
-        }
-    }
-}
-
-fn write_to_database(m: &BTreeMap<String, Product>, conn: &Connection, country: &Country, mut error_str: &mut String) {
-    let max_count = m.len();
-    let mut index = 1;
-
-    for i in m {
-        if let Some(product) = fetch_product_info(i.0.as_str(), country, &mut error_str) {
-            conn.execute("INSERT INTO product (
-                              id,
-                              name,
-                              type,
-                              country,
-                              price,
-                              unit,
-                              metric,
-                              url,
-                              image_url,
-                              department,
-                              category,
-                              subcategory,
-                              department_url,
-                              category_url,
-                              subcategory_url,
-                              created_at,
-                              updated_at
-                          ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, NOW(), NOW())
-                            ON CONFLICT (id, country, url)
-                            DO UPDATE SET
-                                name=$2,
-                                type=$3,
-                                country=$4,
-                                price=$5,
-                                unit=$6,
-                                metric=$7,
-                                url=$8,
-                                image_url=$9,
-                                department=$10,
-                                category=$11,
-                                subcategory=$12,
-                                department_url=$13,
-                                category_url=$14,
-                                subcategory_url=$15,
-                                updated_at=NOW()",
-                             &[
-                                &product.id,
-                                &product.name,
-                                &product.typ,
-                                &product.country,
-                                &product.price,
-                                &product.unit,
-                                &product.metric,
-                                &product.url,
-                                &product.image_url,
-                                &i.1.department,
-                                &i.1.category,
-                                &i.1.subcategory,
-                                &i.1.department_url,
-                                &i.1.category_url,
-                                &i.1.subcategory_url,
-                             ]).unwrap();
-            println!("{}: {}: {}: {} ({}/{})", &i.1.department, &i.1.category, &i.1.subcategory, product.name, index, max_count);
-            index += 1;
-        }
-    }
-}
-
-fn fetch_departments(country: &Country) -> Option<Vec<Department>> {
-    let address = &format!("{}{}", BASE_ADDRESS, &country.url);
-    let ref document = match fetch_html(address) {
-        Ok(doc) => doc,
-        Err(error) => {
-            println!("error: fetch_departments: {:?}", error);
-            return None;
-        }
-    };
-
-    let mut departments = Vec::new();
-
-    let matches = match document.select(".departmentLinkBlock a") {
-        Ok(ms) => ms,
-        Err(error) => {
-            println!("error: fetch_departments: {:?}", error);
-            return None;
-        }
-    };
-
-    for css_match in matches {
-        let node = css_match.as_node();
-
-        let data_ref = match node.data().clone() {
-            Element(data) => data.attributes.borrow().clone(),
-            _ => continue,
-        };
-
-        let url = match data_ref.get("href") {
-            None => continue,
-            Some(url) => url.to_string(),
-        };
-
-        if url == "#" {
-            continue;
-        }
-
-        let text_node = match node.first_child() {
-            Some(text_node) => text_node,
-            None => continue,
-        };
-
-
-        let name = match text_node.as_text() {
-            Some(text) => text.borrow().trim().to_string(),
-            None => continue,
-        };
-
-        departments.push(Department{
-            url: url,
-            name: name,
-        });
-    }
-
-    return Some(departments);
-}
-
-fn fetch_products_from_all_departments(visited_urls: &mut BTreeMap<String, bool>, m: &mut BTreeMap<String, Product>, hierarchy: Vec<Department>, mut error_str: &mut String) {
-    let department = if let Some(department) = hierarchy.last() {
-        department
-    } else {
-        return;
-    };
-
-    let address = &format!("{}{}", BASE_ADDRESS, &department.url);
-    let ref document = match fetch_html(address) {
-        Ok(doc) => doc,
-        Err(error) => {
-            println!("error: fetch_products_from_all_departments: {:?}", error);
-            error_str.push_str(&format!("Failed to fetch HTML at {}\n", address));
-            return;
-        }
-    };
-
-    if has_product(document) {
-        let matches = match document.select("#productLists .productDetails a, .seoProduct") {
-            Ok(ms) => ms,
-            Err(error) => {
-                println!("error: fetch_products_from_all_departments: {:?}", error);
-                error_str.push_str(&format!("Failed to fetch product metadata at {}\n", address));
-                return;
-            }
-        };
-
-        for css_match in matches {
-            let node = css_match.as_node();
-
-            let data_ref = match node.data().clone() {
-                Element(data) => data.attributes.borrow().clone(),
-                _ => continue,
-            };
-
-            let url = match data_ref.get("href") {
-                None => continue,
-                Some(url) => url.to_string(),
-            };
-
-            if url == "#" {
-                continue;
-            }
-
-            println!("PRODUCT URL {}", url);
-
-            let product = Product{
-                id: String::from(""),
-                name: String::from(""),
-                typ: String::from(""),
-                country: String::from(""),
-                price: String::from(""),
-                unit: String::from(""),
-                metric: String::from(""),
-                image_url: String::from(""),
-                url: url.clone(),
-                department: hierarchy[0].name.clone(),
-                category: if hierarchy.len() >= 2 { hierarchy[1].name.clone() } else { "".to_string() },
-                subcategory: if hierarchy.len() >= 3 { hierarchy[2].name.clone() } else { "".to_string() },
-                department_url: hierarchy[0].url.clone(),
-                category_url: if hierarchy.len() >= 2 { hierarchy[1].url.clone() } else { "".to_string() },
-                subcategory_url: if hierarchy.len() >= 3 { hierarchy[2].url.clone() } else { "".to_string() },
-            };
-
-            m.insert(url.clone(), product);
-        }
-    } else {
-        let matches = match document.select(".visualNavContainer a") {
-            Ok(ms) => ms,
-            Err(error) => {
-                println!("error: fetch_products_from_all_departments: {:?}", error);
-                error_str.push_str(&format!("Failed to fetch department data at {}\n", address));
-                return;
-            }
-        };
-
-        for css_match in matches {
-            let node = css_match.as_node();
-
-            let data_ref = match node.data().clone() {
-                Element(data) => data.attributes.borrow().clone(),
-                _ => continue,
-            };
-
-            let mut department = Department {
-                url: "".to_string(),
-                name: "".to_string(),
-            };
-            department.url = match data_ref.get("href") {
-                None => continue,
-                Some(url) => url.to_string(),
-            };
-
-
-            let text = if let Some(text) = fetch_node_text(&node.parent().unwrap(), ".categoryContainer a:first-child") {
-                text
-            } else {
-                continue;
-            };
-
-            department.name = text.clone();
-
-            if visited_urls.contains_key(&department.url) {
-                continue;
-            }
-            visited_urls.insert(department.url.clone(), true);
-
-            let mut next_hierarchy = hierarchy.clone();
-            next_hierarchy.push(department);
-
-            fetch_products_from_all_departments(visited_urls, m, next_hierarchy, &mut error_str);
-        }
-    }
-}
-
-fn has_product(document: &NodeRef) -> bool {
-    let matches = match document.select("#productLists .productDetails, .seoProduct") {
-        Ok(ms) => ms,
-        Err(_) => {
-            return false;
-        }
-    };
-
-    matches.count() > 0
-}
-
-fn fetch_product_info(url: &str, country: &Country, error_str: &mut String) -> Option<Product> {
-    let address = format!("{}{}", BASE_ADDRESS, url);
-    let document = match fetch_html(&address) {
-        Ok(doc) => doc,
-        Err(error) => {
-            error_str.push_str(&format!("Failed to fetch product data at {}\n", &address));
-            println!("error: fetch_product_info: {}", error);
-            return None;
-        }
-    };
-
-    Some(Product {
-        id: fetch_node_text(&document, "#itemNumber").unwrap_or_default().replace(".", ""),
-        name: undup_chars(&fetch_node_text(&document, "#name").unwrap_or_default(), vec![' ']).replace("\n", ""),
-        typ: fetch_node_text(&document, "#type").unwrap_or_default(),
-        price: fetch_node_text(&document, "#price1").unwrap_or_default(),
-        country: country.name.to_string(),
-        unit: fetch_node_text(&document, ".productunit").unwrap_or_default(),
-        metric: fetch_node_text(&document, "#metric").unwrap_or_default(),
-        image_url: fetch_node_attr_value(&document, "#productImg", "src").unwrap_or_default(),
-        url: String::from(url),
-		department: "".to_string(),
-		category: "".to_string(),
-		subcategory: "".to_string(),
-		department_url: "".to_string(),
-		category_url: "".to_string(),
-		subcategory_url: "".to_string(),
-    })
-}
-
-fn fetch_node_text(document: &NodeRef, css_selector: &str) -> Option<String> {
-    let css_matches = match document.select(css_selector) {
-        Ok(css_matches) => css_matches,
-        Err(_) => return None,
-    };
-
-    let css_match = match css_matches.last() {
-        Some(css_match) => css_match,
-        None => return None,
-    };
-
-    let text_node = match css_match.as_node().first_child() {
-        Some(text_node) => text_node,
-        None => return None,
-    };
-
-    match text_node.as_text() {
-        Some(text) => Some(text.borrow().trim().to_string()),
-        None => None,
-    }
-}
-
-fn fetch_node_attr_value(document: &NodeRef, css_selector: &str, name: &str) -> Option<String> {
-    let css_matches = match document.select(css_selector) {
-        Ok(css_matches) => css_matches,
-        Err(_) => return None,
-    };
-
-    let css_match = match css_matches.last() {
-        Some(css_match) => css_match,
-        None => return None,
-    };
-
-    let data = match css_match.as_node().data().clone() {
-        Element(data) => data,
-        _ => return None,
-    };
-
-    let attributes = data.attributes.borrow();
-    match attributes.get(name) {
-        Some(value) => Some(value.to_string()),
-        None => None,
-    }
-}
-
-fn do_file(country: &Country, matches: &Matches) {
-    let output = match matches.opt_str("o") {
-        Some(o) => o,
-        None => "output.csv".to_string(),
-    };
+fn do_file(site: &Site, country: &Country, output_template: &str, concurrency: usize) -> String {
+    let output = output_template.replace("{country}", &country.code);
 
     let mut error_str = String::new();
-    write_department_products(country, Output::File(output), &mut error_str);
+    write_department_products(site, country, Output::File(output), concurrency, &mut error_str);
+    error_str
 }
 
-fn do_database(country: &Country, matches: &Matches) -> String {
+fn do_database(site: &Site, country: &Country, matches: &Matches, concurrency: usize) -> String {
     let dbhost: String = match matches.opt_str("dbhost") {
         Some(t) => t,
         None => "localhost".to_string(),
@@ -504,32 +74,175 @@ fn do_database(country: &Country, matches: &Matches) -> String {
     let conn = Connection::connect(format!("postgres://{}{}@{}:{}", dbuser, dbpass, dbhost, dbport).as_str(), SslMode::None).unwrap();
     let _ = conn.execute(
         "CREATE TABLE product (
-                     id              VARCHAR NOT NULL,
-                     name            VARCHAR NOT NULL,
-                     type            VARCHAR NOT NULL,
-                     country         VARCHAR NOT NULL,
-                     price           VARCHAR NOT NULL,
-                     unit            VARCHAR NOT NULL,
-                     metric          VARCHAR NOT NULL,
-                     url             VARCHAR NOT NULL,
-                     image_url       VARCHAR NOT NULL,
-                     department      VARCHAR NOT NULL,
-                     category        VARCHAR NOT NULL,
-                     subcategory     VARCHAR NOT NULL,
-                     department_url  VARCHAR NOT NULL,
-                     category_url    VARCHAR NOT NULL,
-                     subcategory_url VARCHAR NOT NULL,
-                     created_at      TIMESTAMP WITH TIME ZONE NOT NULL,
-                     updated_at      TIMESTAMP WITH TIME ZONE NOT NULL,
+                     id                VARCHAR NOT NULL,
+                     name              VARCHAR NOT NULL,
+                     type              VARCHAR NOT NULL,
+                     country           VARCHAR NOT NULL,
+                     price             VARCHAR NOT NULL,
+                     unit              VARCHAR NOT NULL,
+                     metric            VARCHAR NOT NULL,
+                     url               VARCHAR NOT NULL,
+                     image_url         VARCHAR NOT NULL,
+                     department        VARCHAR NOT NULL,
+                     category          VARCHAR NOT NULL,
+                     subcategory       VARCHAR NOT NULL,
+                     department_url    VARCHAR NOT NULL,
+                     category_url      VARCHAR NOT NULL,
+                     subcategory_url   VARCHAR NOT NULL,
+                     price_amount      NUMERIC(12, 2),
+                     price_currency    VARCHAR NOT NULL DEFAULT '',
+                     regular_price     NUMERIC(12, 2),
+                     discounted_price  NUMERIC(12, 2),
+                     family_price      NUMERIC(12, 2),
+                     offer_valid_from  DATE,
+                     offer_valid_to    DATE,
+                     width_cm          NUMERIC(10, 2),
+                     depth_cm          NUMERIC(10, 2),
+                     height_cm         NUMERIC(10, 2),
+                     length_cm         NUMERIC(10, 2),
+                     weight_kg         NUMERIC(10, 2),
+                     volume_l          NUMERIC(10, 2),
+                     parent_id         VARCHAR NOT NULL DEFAULT '',
+                     parent_url        VARCHAR NOT NULL DEFAULT '',
+                     buy_online        BOOLEAN,
+                     stock_hint        VARCHAR NOT NULL DEFAULT '',
+                     is_new            BOOLEAN NOT NULL DEFAULT FALSE,
+                     last_chance       BOOLEAN NOT NULL DEFAULT FALSE,
+                     description       TEXT NOT NULL DEFAULT '',
+                     key_features      VARCHAR[] NOT NULL DEFAULT '{}',
+                     materials         TEXT NOT NULL DEFAULT '',
+                     care_instructions TEXT NOT NULL DEFAULT '',
+                     designer          VARCHAR NOT NULL DEFAULT '',
+                     good_to_know      TEXT NOT NULL DEFAULT '',
+                     rating            NUMERIC(3, 2),
+                     review_count      INTEGER,
+                     created_at        TIMESTAMP WITH TIME ZONE NOT NULL,
+                     updated_at        TIMESTAMP WITH TIME ZONE NOT NULL,
                      UNIQUE (id, country, url)
          )", &[]);
+    let _ = conn.batch_execute(
+        "ALTER TABLE product ADD COLUMN IF NOT EXISTS price_amount NUMERIC(12, 2);
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS price_currency VARCHAR NOT NULL DEFAULT '';
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS regular_price NUMERIC(12, 2);
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS discounted_price NUMERIC(12, 2);
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS family_price NUMERIC(12, 2);
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS offer_valid_from DATE;
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS offer_valid_to DATE;
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS width_cm NUMERIC(10, 2);
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS depth_cm NUMERIC(10, 2);
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS height_cm NUMERIC(10, 2);
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS length_cm NUMERIC(10, 2);
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS weight_kg NUMERIC(10, 2);
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS volume_l NUMERIC(10, 2);
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS parent_id VARCHAR NOT NULL DEFAULT '';
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS parent_url VARCHAR NOT NULL DEFAULT '';
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS buy_online BOOLEAN;
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS stock_hint VARCHAR NOT NULL DEFAULT '';
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS is_new BOOLEAN NOT NULL DEFAULT FALSE;
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS last_chance BOOLEAN NOT NULL DEFAULT FALSE;
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS description TEXT NOT NULL DEFAULT '';
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS key_features VARCHAR[] NOT NULL DEFAULT '{}';
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS materials TEXT NOT NULL DEFAULT '';
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS care_instructions TEXT NOT NULL DEFAULT '';
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS designer VARCHAR NOT NULL DEFAULT '';
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS good_to_know TEXT NOT NULL DEFAULT '';
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS rating NUMERIC(3, 2);
+         ALTER TABLE product ADD COLUMN IF NOT EXISTS review_count INTEGER;");
+    let _ = conn.execute(
+        "CREATE TABLE product_image (
+                     product_id  VARCHAR NOT NULL,
+                     country     VARCHAR NOT NULL,
+                     product_url VARCHAR NOT NULL,
+                     position    INTEGER NOT NULL,
+                     url         VARCHAR NOT NULL,
+                     large_url   VARCHAR NOT NULL,
+                     zoom_url    VARCHAR NOT NULL,
+                     local_path  VARCHAR NOT NULL DEFAULT '',
+                     sha256      VARCHAR NOT NULL DEFAULT '',
+                     UNIQUE (product_id, country, product_url, position)
+         )", &[]);
+    let _ = conn.batch_execute(
+        "ALTER TABLE product_image ADD COLUMN IF NOT EXISTS local_path VARCHAR NOT NULL DEFAULT '';
+         ALTER TABLE product_image ADD COLUMN IF NOT EXISTS sha256 VARCHAR NOT NULL DEFAULT '';");
+    let _ = conn.execute(
+        "CREATE TABLE product_document (
+                     product_id  VARCHAR NOT NULL,
+                     country     VARCHAR NOT NULL,
+                     product_url VARCHAR NOT NULL,
+                     position    INTEGER NOT NULL,
+                     url         VARCHAR NOT NULL,
+                     kind        VARCHAR NOT NULL,
+                     language    VARCHAR NOT NULL,
+                     local_path  VARCHAR NOT NULL DEFAULT '',
+                     sha256      VARCHAR NOT NULL DEFAULT '',
+                     UNIQUE (product_id, country, product_url, position)
+         )", &[]);
+    let _ = conn.execute(
+        "CREATE TABLE product_package (
+                     product_id  VARCHAR NOT NULL,
+                     country     VARCHAR NOT NULL,
+                     product_url VARCHAR NOT NULL,
+                     position    INTEGER NOT NULL,
+                     item_number VARCHAR NOT NULL,
+                     quantity    INTEGER NOT NULL,
+                     width_cm    NUMERIC(10, 2),
+                     height_cm   NUMERIC(10, 2),
+                     length_cm   NUMERIC(10, 2),
+                     weight_kg   NUMERIC(10, 2),
+                     UNIQUE (product_id, country, product_url, position)
+         )", &[]);
+    let _ = conn.execute(
+        "CREATE TABLE product_variant (
+                     product_id  VARCHAR NOT NULL,
+                     country     VARCHAR NOT NULL,
+                     product_url VARCHAR NOT NULL,
+                     variant_url VARCHAR NOT NULL,
+                     UNIQUE (product_id, country, product_url, variant_url)
+         )", &[]);
+    let _ = conn.execute(
+        "CREATE TABLE product_availability (
+                     product_id  VARCHAR NOT NULL,
+                     country     VARCHAR NOT NULL,
+                     product_url VARCHAR NOT NULL,
+                     buy_online  BOOLEAN,
+                     stock_hint  VARCHAR NOT NULL,
+                     checked_at  TIMESTAMP WITH TIME ZONE NOT NULL
+         )", &[]);
+    let _ = conn.execute(
+        "CREATE TABLE product_rating (
+                     product_id   VARCHAR NOT NULL,
+                     country      VARCHAR NOT NULL,
+                     product_url  VARCHAR NOT NULL,
+                     rating       NUMERIC(3, 2),
+                     review_count INTEGER,
+                     price_amount NUMERIC(12, 2),
+                     checked_at   TIMESTAMP WITH TIME ZONE NOT NULL
+         )", &[]);
 
     let mut error_str = String::new();
-    write_department_products(country, Output::Database(conn), &mut error_str);
+    write_department_products(site, country, Output::Database(Box::new(conn)), concurrency, &mut error_str);
     error_str
 }
 
-fn report_error(error_str: &str, emails: &Vec<String>) -> Result<Response> {
+fn do_compare(countries: &[&Country], matches: &Matches) -> result::Result<(), String> {
+    let input_template = matches.opt_str("input").unwrap_or_else(|| "output-{country}.csv".to_string());
+    let output = matches.opt_str("o").unwrap_or_else(|| "comparison.csv".to_string());
+
+    let rates = match matches.opt_str("rates") {
+        Some(path) => load_rates(&PathBuf::from(path))?,
+        None => return Err("Comparing prices needs an exchange rate file passed to --rates!".to_string()),
+    };
+
+    let mut markets = Vec::new();
+    for country in countries {
+        let input = input_template.replace("{country}", &country.code);
+        markets.push((*country, read_listings(&PathBuf::from(input))?));
+    }
+
+    write_comparison(&output, &markets, &rates)
+}
+
+fn report_error(error_str: &str, country: &Country, emails: &[String]) -> Result<Response> {
     let client = Client::new();
 
     // Format emails into query format
@@ -538,35 +251,64 @@ fn report_error(error_str: &str, emails: &Vec<String>) -> Result<Response> {
         formatted_emails.push_str(&format!("&to={}", &email));
     }
 
-    let message = percent_encode(format!("http://email.bbh-labs.com.sg?from=BBH Labs <postmaster@mail.bbh-labs.com.sg>&subject=Error: IKEA Spider&text={}{}", error_str, formatted_emails).as_bytes(), QUERY_ENCODE_SET).collect::<String>();
-    let res = try!(client.post(&message).send());
+    let message = percent_encode(format!("http://email.bbh-labs.com.sg?from=BBH Labs <postmaster@mail.bbh-labs.com.sg>&subject=Error: IKEA Spider ({})&text={}{}", country.name, error_str, formatted_emails).as_bytes(), QUERY_ENCODE_SET).collect::<String>();
+    let res = client.post(&message).send()?;
     Ok(res)
 }
 
+fn parse_opt<T: FromStr>(matches: &Matches, name: &str, default: T) -> result::Result<T, String> {
+    match matches.opt_str(name) {
+        Some(t) => t.parse::<T>().map_err(|_| format!("Argument passed to --{} is not a number!", name)),
+        None => Ok(default),
+    }
+}
+
+fn retry_policy(matches: &Matches) -> result::Result<RetryPolicy, String> {
+    let default = RetryPolicy::default();
+
+    let mut retryable_statuses = Vec::new();
+    for status in matches.opt_strs("retry-status") {
+        match status.parse::<u16>() {
+            Ok(code) => retryable_statuses.push(StatusCode::from_u16(code)),
+            Err(_) => return Err("Argument passed to --retry-status is not a number!".to_string()),
+        }
+    }
+    if retryable_statuses.is_empty() {
+        retryable_statuses = default.retryable_statuses;
+    }
+
+    Ok(RetryPolicy {
+        max_attempts: parse_opt(matches, "retries", default.max_attempts)?.max(1),
+        base_delay: Duration::from_millis(parse_opt(matches, "retry-delay", default.base_delay.as_millis() as u64)?),
+        jitter: Duration::from_millis(parse_opt(matches, "retry-jitter", default.jitter.as_millis() as u64)?),
+        max_delay: Duration::from_millis(parse_opt(matches, "retry-max-delay", default.max_delay.as_millis() as u64)?),
+        retryable_statuses,
+    })
+}
+
+/// Opens the mirror directory passed to option `name`, if any.
+fn open_mirror(matches: &Matches, name: &str) -> result::Result<Option<Mirror>, String> {
+    match matches.opt_str(name) {
+        Some(dir) => Mirror::new(PathBuf::from(&dir))
+            .map(Some)
+            .map_err(|error| format!("Failed to open {} directory {}: {}", name, dir, error)),
+        None => Ok(None),
+    }
+}
+
 fn print_usage(program: &str, opts: Options) {
     let brief = format!("Usage: {} [options]", program);
     print!("{}", opts.usage(&brief));
 }
 
 fn print_countries(countries: &[Country]) {
-    let mut i = 0;
-
-    println!("Select a country index from the following list (specify using -c flag):");
+    println!("Select a country code from the following list (specify using -c flag):");
     for country in countries {
-        println!("{}: {}", i, &country.name);
-        i += 1;
+        println!("{}: {}", &country.code, &country.name);
     }
 }
 
 fn main() {
-    let countries = [
-        Country { name: "Singapore", url: "/sg/en" },
-        Country { name: "Malaysia English", url: "/my/en" },
-        Country { name: "Malaysia Bahasa", url: "/my/ms" },
-        Country { name: "Thailand Thai", url: "/th/th" },
-        Country { name: "Thailand English", url: "/th/en" },
-    ];
-
     // Parse program arguments
     let args: Vec<String> = env::args().collect();
     let program = args[0].clone();
@@ -574,16 +316,32 @@ fn main() {
     let mut opts = Options::new();
     opts.optopt("t",
                 "type",
-                "set type of backend",
+                "set type of backend: file, database, or compare to join the file outputs of several countries by item number",
                 "TYPE");
     opts.optopt("o",
                 "output",
-                "set output file name",
+                "set output file name, {country} is replaced by the country code (default: output.csv, or output-{country}.csv for several countries)",
                 "FILE");
     opts.optopt("c",
                 "country",
-                "set country index",
-                "COUNTRY INDEX");
+                "set comma-separated country codes, or all",
+                "CODES");
+    opts.optopt("",
+                "countries",
+                "load the country catalogue from a TOML or JSON file",
+                "FILE");
+    opts.optopt("",
+                "selectors",
+                "load selector profiles from a TOML file, on top of the built-in ones",
+                "FILE");
+    opts.optopt("",
+                "input",
+                "set file outputs read by -t compare, {country} is replaced by the country code (default: output-{country}.csv)",
+                "FILE");
+    opts.optopt("",
+                "rates",
+                "set TOML file of exchange rates used by -t compare",
+                "FILE");
     opts.optopt("",
                 "dbhost",
                 "set database host",
@@ -604,31 +362,106 @@ fn main() {
                 "interval",
                 "set loop interval in seconds (default: 60)",
                 "SECS");
+    opts.optopt("",
+                "retries",
+                "set maximum fetch attempts per page (default: 3)",
+                "COUNT");
+    opts.optopt("",
+                "retry-delay",
+                "set base retry delay in milliseconds, doubled on every retry (default: 500)",
+                "MILLIS");
+    opts.optopt("",
+                "retry-jitter",
+                "set maximum random delay added to every retry in milliseconds (default: 250)",
+                "MILLIS");
+    opts.optopt("",
+                "retry-max-delay",
+                "set longest wait before a retry in milliseconds; a longer Retry-After is not retried (default: 60000)",
+                "MILLIS");
+    opts.optmulti("",
+                  "retry-status",
+                  "retry pages answered with this HTTP status (default: 429, 500, 502, 503, 504)",
+                  "STATUS");
+    opts.optopt("",
+                "rate",
+                "set maximum requests per second to each host, 0 to disable (default: 2)",
+                "REQS");
+    opts.optopt("",
+                "burst",
+                "set number of requests allowed back to back before --rate applies (default: 4)",
+                "COUNT");
+    opts.optopt("",
+                "concurrency",
+                "set number of product pages fetched in parallel (default: 4)",
+                "COUNT");
+    opts.optopt("",
+                "base-url",
+                "set site to crawl, which may sit under a path such as http://host/mirror/ (default: http://www.ikea.com)",
+                "URL");
+    opts.optopt("",
+                "cache-dir",
+                "cache fetched pages in this directory",
+                "DIR");
+    opts.optopt("",
+                "cache-ttl",
+                "set seconds before a cached page is revalidated (default: 86400)",
+                "SECS");
+    opts.optflag("", "offline", "only serve pages from --cache-dir, never fetch");
+    opts.optopt("",
+                "record",
+                "save every fetched page into this directory",
+                "DIR");
+    opts.optopt("",
+                "replay",
+                "serve pages saved with --record from this directory instead of the network",
+                "DIR");
+    opts.optopt("",
+                "images",
+                "download product images into this directory",
+                "DIR");
+    opts.optopt("",
+                "documents",
+                "download assembly instructions, manuals and product information into this directory",
+                "DIR");
     opts.optmulti("e", "email", "email to this address if there's an error", "EMAIL");
     opts.optflag("l", "loop", "forever scrape the website");
     opts.optflag("h", "help", "print this help menu");
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
-        Err(f) => panic!(f.to_string()),
+        Err(f) => panic!("{}", f),
     };
     if matches.opt_present("h") {
         print_usage(&program, opts);
         return;
     }
 
-    let country = match matches.opt_str("c") {
-        Some(index_str) => {
-            if let Ok(index) = index_str.parse::<usize>() {
-                if index > countries.len() - 1{
-                    println!("Country index is too big!");
-                    return;
-                }
-                &countries[index]
-            } else {
-                println!("Invalid country index!");
+    let countries = match matches.opt_str("countries") {
+        Some(path) => match load_countries(&PathBuf::from(path)) {
+            Ok(countries) => countries,
+            Err(error) => {
+                println!("Failed to load countries: {}", error);
                 return;
+            },
+        },
+        None => default_countries(),
+    };
+
+    let selected: Vec<&Country> = match matches.opt_str("c") {
+        Some(ref codes) if codes == "all" => countries.iter().collect(),
+        Some(codes) => {
+            let mut selected = Vec::new();
+            for code in codes.split(',') {
+                match find_country(&countries, code.trim()) {
+                    Some(country) => selected.push(country),
+                    None => {
+                        println!("Unknown country code {}!", code);
+                        print_countries(&countries);
+                        return;
+                    },
+                }
             }
+            selected
         },
         None => {
             print_countries(&countries);
@@ -641,6 +474,45 @@ fn main() {
         None => "file".to_string(),
     };
 
+    if typ == "compare" {
+        if let Err(error) = do_compare(&selected, &matches) {
+            println!("Failed to compare prices: {}", error);
+        }
+        return;
+    }
+
+    let profiles = match matches.opt_str("selectors") {
+        Some(path) => match load_profiles(&PathBuf::from(path)) {
+            Ok(profiles) => profiles,
+            Err(error) => {
+                println!("Failed to load selectors: {}", error);
+                return;
+            },
+        },
+        None => default_profiles(),
+    };
+
+    let mut crawls: Vec<(&Country, Profile)> = Vec::new();
+    for country in &selected {
+        match profiles.get(&country.selectors) {
+            Some(profile) => crawls.push((*country, profile)),
+            None => {
+                println!("Unknown selector profile {} for {}!", country.selectors, country.code);
+                return;
+            },
+        }
+    }
+
+    let output_template = match matches.opt_str("o") {
+        Some(o) => o,
+        None if selected.len() > 1 => "output-{country}.csv".to_string(),
+        None => "output.csv".to_string(),
+    };
+    if typ == "file" && selected.len() > 1 && !output_template.contains("{country}") {
+        println!("Argument passed to -o or --output needs {{country}} when crawling several countries!");
+        return;
+    }
+
     let interval = match matches.opt_str("i") {
         Some(t) => match t.parse::<u64>() {
             Ok(secs) => secs,
@@ -654,32 +526,141 @@ fn main() {
 
     let emails = matches.opt_strs("e");
     {
-        if emails.len() > 0 {
+        if !emails.is_empty() {
             print!("Will email errors to: ");
             for email in &emails {
                 print!("{} ", &email);
             }
-            println!("");
+            println!();
         }
     }
 
+    let retry_policy = match retry_policy(&matches) {
+        Ok(policy) => policy,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        },
+    };
+
+    let rate = match parse_opt(&matches, "rate", 2.0) {
+        Ok(rate) => rate,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        },
+    };
+
+    let burst = match parse_opt(&matches, "burst", 4) {
+        Ok(burst) => burst,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        },
+    };
+
+    let concurrency = match parse_opt(&matches, "concurrency", 4) {
+        Ok(concurrency) => concurrency,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        },
+    };
+
+    let cache_ttl = match parse_opt(&matches, "cache-ttl", 86400) {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(message) => {
+            println!("{}", message);
+            return;
+        },
+    };
+
+    let offline = matches.opt_present("offline");
+    if offline && !matches.opt_present("cache-dir") {
+        println!("--offline requires --cache-dir!");
+        return;
+    }
+
+    let mut fetcher: Box<dyn Fetcher> = match matches.opt_str("replay") {
+        Some(dir) => match ReplayFetcher::new(PathBuf::from(&dir)) {
+            Ok(replay) => Box::new(replay),
+            Err(error) => {
+                println!("Failed to load recording from {}: {}", dir, error);
+                return;
+            },
+        },
+        None => Box::new(RetryFetcher::new(RateLimitedFetcher::new(HyperFetcher::new(), rate, burst), retry_policy)),
+    };
+
+    if let Some(dir) = matches.opt_str("cache-dir") {
+        fetcher = Box::new(CachingFetcher::new(fetcher, PathBuf::from(dir), cache_ttl, offline));
+    }
+
+    if let Some(dir) = matches.opt_str("record") {
+        fetcher = match RecordingFetcher::new(fetcher, PathBuf::from(&dir)) {
+            Ok(recorder) => Box::new(recorder),
+            Err(error) => {
+                println!("Failed to record into {}: {}", dir, error);
+                return;
+            },
+        };
+    }
+
+    let base_url = match Url::parse(&matches.opt_str("base-url").unwrap_or_else(|| BASE_ADDRESS.to_string())) {
+        Ok(url) => url,
+        Err(error) => {
+            println!("Argument passed to --base-url is not a valid URL: {}", error);
+            return;
+        },
+    };
+
+    let image_mirror = match open_mirror(&matches, "images") {
+        Ok(mirror) => mirror,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        },
+    };
+    let document_mirror = match open_mirror(&matches, "documents") {
+        Ok(mirror) => mirror,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        },
+    };
+
+    let mut site = Site::new(&*fetcher, base_url);
+    site.image_mirror = image_mirror.as_ref();
+    site.document_mirror = document_mirror.as_ref();
+
     loop {
         let start_time = Instant::now();
 
-        if typ == "file" {
-            do_file(country, &matches);
-        } else if typ == "database" {
-            let error_str = &do_database(country, &matches);
-            if error_str.len() > 0 {
-                match report_error(error_str, &emails) {
-                    Ok(res) => if res.status == StatusCode::Ok {
-                        println!("Successfully reported error");
-                    } else {
-                        println!("Failed to report error: {}", res.status);
-                    },
-                    Err(err) => {
-                        println!("Failed to report error: {}", err);
-                    },
+        for &(country, ref profile) in &crawls {
+            site.selectors = profile.clone();
+
+            let error_str = if typ == "file" {
+                do_file(&site, country, &output_template, concurrency)
+            } else if typ == "database" {
+                do_database(&site, country, &matches, concurrency)
+            } else {
+                String::new()
+            };
+
+            if !error_str.is_empty() {
+                print!("Errors for {}:\n{}", &country.name, error_str);
+
+                if !emails.is_empty() {
+                    match report_error(&error_str, country, &emails) {
+                        Ok(res) => if res.status == StatusCode::Ok {
+                            println!("Successfully reported error");
+                        } else {
+                            println!("Failed to report error: {}", res.status);
+                        },
+                        Err(err) => {
+                            println!("Failed to report error: {}", err);
+                        },
+                    }
                 }
             }
         }
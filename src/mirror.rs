@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::BufReader;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Hyper
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+
+// SHA-2
+use sha2::{Digest, Sha256};
+
+// Spider
+use cache::temporary_path;
+use country::Country;
+use fetcher::{Error, Fetcher, Page, Result};
+use {Product, Site};
+
+const INDEX_FILE: &str = "index";
+
+/// What an earlier run learned about a downloaded URL.
+#[derive(Clone)]
+struct Download {
+    hash: String,
+    etag: String,
+    last_modified: String,
+}
+
+/// Keeps a local copy of the images or documents of every product.
+///
+/// Pictures are saved as `{country}/{item}/{n}.jpg` and documents as
+/// `{country}/{item}/{kind}-{n}.pdf`, numbered from 1 in page order. `item`
+/// is the item number, or a hash of the product URL when the page gave none.
+/// Contents are stored once under `objects/`, named by their SHA-256, and
+/// the tree holds hard links to them, so a file shared by several products
+/// or countries takes its space once. `index` records the hash and
+/// validators of every URL downloaded, one tab-separated line each, the last
+/// line winning, so later runs revalidate instead of downloading.
+pub struct Mirror {
+    dir: PathBuf,
+    downloads: Mutex<BTreeMap<String, Download>>,
+    index: Mutex<File>,
+}
+
+impl Mirror {
+    pub fn new(dir: PathBuf) -> io::Result<Mirror> {
+        fs::create_dir_all(dir.join("objects"))?;
+
+        let mut downloads = BTreeMap::new();
+        if let Ok(f) = File::open(dir.join(INDEX_FILE)) {
+            for line in BufReader::new(f).lines() {
+                let line = line?;
+                let fields: Vec<&str> = line.split('\t').collect();
+                if let [url, hash, etag, last_modified] = fields[..] {
+                    downloads.insert(url.to_string(), Download {
+                        hash: hash.to_string(),
+                        etag: etag.to_string(),
+                        last_modified: last_modified.to_string(),
+                    });
+                }
+            }
+        }
+
+        let index = OpenOptions::new().create(true).append(true).open(dir.join(INDEX_FILE))?;
+
+        Ok(Mirror {
+            dir,
+            downloads: Mutex::new(downloads),
+            index: Mutex::new(index),
+        })
+    }
+
+    /// Downloads the largest rendition of every image of `product` and
+    /// records where it was saved and its hash.
+    pub fn download_images(&self, site: &Site, country: &Country, product: &mut Product, error_str: &mut String) {
+        let dir = product_dir(product);
+        for (n, image) in product.images.iter_mut().enumerate() {
+            let source = [&image.zoom, &image.large, &image.url].iter().find(|url| !url.is_empty()).map(|url| url.to_string()).unwrap_or_default();
+            let address = site.address(&source);
+            let path = format!("{}/{}/{}.jpg", country.code, dir, n + 1);
+
+            match self.download(site.fetcher, &address, &path) {
+                Ok(hash) => {
+                    image.path = path;
+                    image.hash = hash;
+                },
+                Err(error) => {
+                    error_str.push_str(&format!("Failed to download image at {}\n", &address));
+                    println!("error: download_images: {}", error);
+                },
+            }
+        }
+    }
+
+    /// Downloads every document of `product` and records where it was saved
+    /// and its hash.
+    pub fn download_documents(&self, site: &Site, country: &Country, product: &mut Product, error_str: &mut String) {
+        let dir = product_dir(product);
+        for (n, document) in product.documents.iter_mut().enumerate() {
+            let address = site.address(&document.url);
+            let path = format!("{}/{}/{}-{}.pdf", country.code, dir, document.kind, n + 1);
+
+            match self.download(site.fetcher, &address, &path) {
+                Ok(hash) => {
+                    document.path = path;
+                    document.hash = hash;
+                },
+                Err(error) => {
+                    error_str.push_str(&format!("Failed to download document at {}\n", &address));
+                    println!("error: download_documents: {}", error);
+                },
+            }
+        }
+    }
+
+    /// Saves `url` as `path` under the mirror and returns its hash.
+    fn download(&self, fetcher: &dyn Fetcher, url: &str, path: &str) -> Result<String> {
+        let known = self.downloads.lock().unwrap().get(url).cloned()
+            .filter(|download| self.object(&download.hash).exists());
+
+        let mut headers = Headers::new();
+        if let Some(ref download) = known {
+            if !download.etag.is_empty() {
+                headers.set_raw("If-None-Match", vec![download.etag.clone().into_bytes()]);
+            }
+            if !download.last_modified.is_empty() {
+                headers.set_raw("If-Modified-Since", vec![download.last_modified.clone().into_bytes()]);
+            }
+        }
+
+        let page = fetcher.fetch(url, &headers)?;
+
+        let hash = match known {
+            Some(download) if page.status == StatusCode::NotModified => download.hash,
+            _ if page.status.is_success() => self.store(url, &page)?,
+            _ => return Err(Error::Status(page.status)),
+        };
+
+        self.link(&hash, &self.dir.join(path))?;
+        Ok(hash)
+    }
+
+    /// Writes the body of `page` into the object store unless it is already
+    /// there, and remembers it as the current content of `url`.
+    fn store(&self, url: &str, page: &Page) -> io::Result<String> {
+        let hash = format!("{:x}", Sha256::digest(&page.body));
+
+        let object = self.object(&hash);
+        if !object.exists() {
+            let tmp = temporary_path(&object);
+            File::create(&tmp)?.write_all(&page.body)?;
+            fs::rename(&tmp, &object)?;
+        }
+
+        let download = Download {
+            hash: hash.clone(),
+            etag: header(page, "ETag"),
+            last_modified: header(page, "Last-Modified"),
+        };
+
+        let mut downloads = self.downloads.lock().unwrap();
+        let unchanged = downloads.get(url).is_some_and(|known| {
+            known.hash == download.hash && known.etag == download.etag && known.last_modified == download.last_modified
+        });
+        if !unchanged {
+            let mut index = self.index.lock().unwrap();
+            index.write_all(format!("{}\t{}\t{}\t{}\n", url, download.hash, download.etag, download.last_modified).as_bytes())?;
+            downloads.insert(url.to_string(), download);
+        }
+
+        Ok(hash)
+    }
+
+    /// Points `target` at the object named `hash`, leaving it alone when it
+    /// already holds the same content.
+    fn link(&self, hash: &str, target: &Path) -> io::Result<()> {
+        if target.exists() {
+            let mut contents = Vec::new();
+            File::open(target)?.read_to_end(&mut contents)?;
+            if format!("{:x}", Sha256::digest(&contents)) == hash {
+                return Ok(());
+            }
+            fs::remove_file(target)?;
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let object = self.object(hash);
+        fs::hard_link(&object, target).or_else(|_| fs::copy(&object, target).map(|_| ()))
+    }
+
+    fn object(&self, hash: &str) -> PathBuf {
+        self.dir.join("objects").join(hash)
+    }
+}
+
+/// Directory of a product: its item number reduced to `[A-Za-z0-9-]`, so
+/// page text cannot reach outside the tree, or failing that `url-` and the
+/// start of the SHA-256 of its URL.
+fn product_dir(product: &Product) -> String {
+    let id: String = product.id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '-' })
+        .collect();
+
+    if id.chars().any(|c| c.is_ascii_alphanumeric()) {
+        id
+    } else {
+        format!("url-{}", &format!("{:x}", Sha256::digest(product.url.as_bytes()))[..16])
+    }
+}
+
+fn header(page: &Page, name: &str) -> String {
+    page.headers.get_raw(name)
+        .and_then(|values| values.first())
+        .map(|value| String::from_utf8_lossy(value).into_owned())
+        .unwrap_or_default()
+}
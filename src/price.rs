@@ -0,0 +1,97 @@
+// Spider
+use country::Country;
+
+/// A displayed price read into a decimal amount and an ISO 4217 currency code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Price {
+    pub amount: f64,
+    pub currency: String,
+}
+
+/// Reads the amount out of a displayed price such as "$1,299.00",
+/// "RM 1.299,00" or "฿1,290". Only the first number is read, so "$5.99 /
+/// 100 pack" is 5.99 and "4.6 out of 5" is 4.6.
+///
+/// When both '.' and ',' appear, the last one is the decimal point. Otherwise
+/// a separator is the decimal point only if it is `decimal` and appears once;
+/// anything else groups thousands, as does `grouping` when it is another
+/// character such as a space.
+pub fn parse_amount(raw: &str, decimal: char, grouping: char) -> Option<f64> {
+    let start = raw.find(|c: char| c.is_ascii_digit())?;
+
+    // A separator belongs to the number only when a digit follows it.
+    let mut digits = String::new();
+    let mut chars = raw[start..].chars().peekable();
+    while let Some(c) = chars.next() {
+        let before_digit = chars.peek().is_some_and(|next| next.is_ascii_digit());
+        if c.is_ascii_digit() || ((c == '.' || c == ',') && before_digit) {
+            digits.push(c);
+        } else if !(c == grouping && before_digit) {
+            break;
+        }
+    }
+
+    let point = digits.rfind(['.', ',']).filter(|&pos| {
+        let separator = digits.as_bytes()[pos] as char;
+        let other = if separator == '.' { ',' } else { '.' };
+        digits.contains(other) || (separator == decimal && digits.matches(separator).count() == 1)
+    });
+
+    let number = match point {
+        Some(pos) => {
+            let whole: String = digits[..pos].chars().filter(|c| c.is_ascii_digit()).collect();
+            format!("{}.{}", whole, &digits[pos + 1..])
+        },
+        None => digits.chars().filter(|c| c.is_ascii_digit()).collect(),
+    };
+
+    number.parse::<f64>().ok()
+}
+
+/// Parses a price displayed on a country's site, using the country's
+/// separators and its currency for the code.
+pub fn parse_price(raw: &str, country: &Country) -> Option<Price> {
+    parse_amount(raw, country.decimal, country.grouping).map(|amount| Price {
+        amount,
+        currency: country.currency.clone(),
+    })
+}
+
+/// Reads an offer date such as "31.01.2019", "31/01/2019" or "2019-01-31"
+/// into ISO 8601 form. Dates that do not lead with the year are read day first.
+pub fn parse_date(raw: &str) -> Option<String> {
+    let parts: Vec<&str> = raw.split(|c: char| !c.is_ascii_digit()).filter(|part| !part.is_empty()).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+
+    let (year, month, day) = if parts[0].len() == 4 {
+        (parts[0], parts[1], parts[2])
+    } else {
+        (parts[2], parts[1], parts[0])
+    };
+
+    let mut year: u32 = year.parse().ok()?;
+    let month: u32 = month.parse().ok()?;
+    let day: u32 = day.parse().ok()?;
+
+    if year < 100 {
+        year += 2000;
+    }
+    if !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+
+    Some(format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+/// Number of days in `month` of `year`, or 0 for a month that does not exist.
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+// Hyper
+use hyper::header::Headers;
+
+// URL
+use url::Url;
+
+// Fetcher
+use fetcher::{Fetcher, Page, Result};
+
+/// Token bucket holding the request budget of a single host.
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Wraps another fetcher and limits how fast each host is requested.
+///
+/// Every host gets its own token bucket refilled at `rate` tokens per second
+/// and holding at most `burst` tokens. A rate of zero disables the limiter.
+pub struct RateLimitedFetcher<F> {
+    inner: F,
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<BTreeMap<String, Bucket>>,
+}
+
+impl<F: Fetcher> RateLimitedFetcher<F> {
+    pub fn new(inner: F, rate: f64, burst: u32) -> RateLimitedFetcher<F> {
+        RateLimitedFetcher {
+            inner,
+            rate,
+            burst: f64::from(burst.max(1)),
+            buckets: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Takes a token for the host, returning how long to wait before using it.
+    fn reserve(&self, host: &str) -> Duration {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let burst = self.burst;
+        let bucket = buckets.entry(host.to_string()).or_insert_with(|| Bucket {
+            tokens: burst,
+            updated_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated_at = now;
+
+        // Tokens may go negative: later callers queue up behind this one.
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.rate)
+        }
+    }
+}
+
+impl<F: Fetcher> Fetcher for RateLimitedFetcher<F> {
+    fn fetch(&self, url: &str, headers: &Headers) -> Result<Page> {
+        if self.rate > 0.0 {
+            let host = match Url::parse(url) {
+                Ok(parsed) => parsed.host_str().unwrap_or_default().to_string(),
+                Err(_) => String::new(),
+            };
+
+            let delay = self.reserve(&host);
+            if delay > Duration::from_secs(0) {
+                sleep(delay);
+            }
+        }
+
+        self.inner.fetch(url, headers)
+    }
+}
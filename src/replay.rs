@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::BufReader;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Hyper
+use hyper::header::Headers;
+
+// Fetcher
+use cache::{key, now_secs, read_entry, write_entry, Entry};
+use fetcher::{Error, Fetcher, Page, Result};
+
+const INDEX_FILE: &str = "index";
+
+/// Wraps another fetcher and saves every page it returns into a directory.
+///
+/// Pages are stored under `pages/` in the cache entry format, whatever their
+/// status, and `index` maps each URL to its page file, one tab-separated
+/// pair per line. A URL fetched twice is recorded twice; the last line wins.
+pub struct RecordingFetcher<F> {
+    inner: F,
+    dir: PathBuf,
+    index: Mutex<File>,
+}
+
+impl<F: Fetcher> RecordingFetcher<F> {
+    pub fn new(inner: F, dir: PathBuf) -> io::Result<RecordingFetcher<F>> {
+        fs::create_dir_all(dir.join("pages"))?;
+        let index = OpenOptions::new().create(true).append(true).open(dir.join(INDEX_FILE))?;
+
+        Ok(RecordingFetcher {
+            inner,
+            dir,
+            index: Mutex::new(index),
+        })
+    }
+
+    fn record(&self, url: &str, page: &Page) -> io::Result<()> {
+        let file = format!("pages/{}", key(url));
+
+        write_entry(&self.dir.join(&file), &Entry {
+            url: url.to_string(),
+            fetched_at: now_secs(),
+            page: page.clone(),
+        })?;
+
+        let mut index = self.index.lock().unwrap();
+        index.write_all(format!("{}\t{}\n", url, file).as_bytes())
+    }
+}
+
+impl<F: Fetcher> Fetcher for RecordingFetcher<F> {
+    fn fetch(&self, url: &str, headers: &Headers) -> Result<Page> {
+        let page = self.inner.fetch(url, headers)?;
+
+        if let Err(error) = self.record(url, &page) {
+            println!("error: record: failed to record {}: {}", url, error);
+        }
+
+        Ok(page)
+    }
+}
+
+/// Serves pages saved by `RecordingFetcher` and never touches the network.
+pub struct ReplayFetcher {
+    dir: PathBuf,
+    index: BTreeMap<String, String>,
+}
+
+impl ReplayFetcher {
+    pub fn new(dir: PathBuf) -> io::Result<ReplayFetcher> {
+        let mut index = BTreeMap::new();
+
+        let f = File::open(dir.join(INDEX_FILE))?;
+        for line in BufReader::new(f).lines() {
+            let line = line?;
+            let mut fields = line.splitn(2, '\t');
+            if let (Some(url), Some(file)) = (fields.next(), fields.next()) {
+                index.insert(url.to_string(), file.to_string());
+            }
+        }
+
+        Ok(ReplayFetcher {
+            dir,
+            index,
+        })
+    }
+}
+
+impl Fetcher for ReplayFetcher {
+    fn fetch(&self, url: &str, _headers: &Headers) -> Result<Page> {
+        let file = match self.index.get(url) {
+            Some(file) => file,
+            None => return Err(Error::Missing(url.to_string())),
+        };
+
+        Ok(read_entry(&self.dir.join(file))?.page)
+    }
+}
@@ -0,0 +1,137 @@
+use std::str;
+use std::thread::sleep;
+use std::time::Duration;
+
+// Hyper
+use hyper::header::{Headers, HttpDate};
+use hyper::status::StatusCode;
+
+// Rand
+use rand;
+
+// Time
+use time;
+
+// Fetcher
+use fetcher::{Fetcher, Page, Result};
+
+/// Controls how failed fetches are retried.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled on every following retry.
+    pub base_delay: Duration,
+    /// Upper bound of the random delay added to every backoff.
+    pub jitter: Duration,
+    /// Longest wait before a retry. Backoffs are cut to it; a server asking
+    /// to wait longer with `Retry-After` is not retried.
+    pub max_delay: Duration,
+    /// Statuses that are worth asking for again.
+    pub retryable_statuses: Vec<StatusCode>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            jitter: Duration::from_millis(250),
+            max_delay: Duration::from_secs(60),
+            retryable_statuses: vec![
+                StatusCode::TooManyRequests,
+                StatusCode::InternalServerError,
+                StatusCode::BadGateway,
+                StatusCode::ServiceUnavailable,
+                StatusCode::GatewayTimeout,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with jitter for the given retry (1 for the first retry).
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u32.checked_shl(retry - 1).unwrap_or(u32::MAX);
+        let delay = self.base_delay.checked_mul(factor).unwrap_or(Duration::MAX);
+
+        let jitter_ms = self.jitter.as_millis() as u64;
+        if jitter_ms == 0 {
+            return delay.min(self.max_delay);
+        }
+
+        delay.saturating_add(Duration::from_millis(rand::random::<u64>() % (jitter_ms + 1))).min(self.max_delay)
+    }
+}
+
+/// Wraps another fetcher and retries transport errors and retryable statuses.
+pub struct RetryFetcher<F> {
+    inner: F,
+    policy: RetryPolicy,
+}
+
+impl<F: Fetcher> RetryFetcher<F> {
+    pub fn new(inner: F, policy: RetryPolicy) -> RetryFetcher<F> {
+        RetryFetcher {
+            inner,
+            policy,
+        }
+    }
+}
+
+impl<F: Fetcher> Fetcher for RetryFetcher<F> {
+    fn fetch(&self, url: &str, headers: &Headers) -> Result<Page> {
+        let mut attempt = 1;
+
+        loop {
+            let delay = match self.inner.fetch(url, headers) {
+                Ok(page) => {
+                    if attempt >= self.policy.max_attempts || !self.policy.retryable_statuses.contains(&page.status) {
+                        return Ok(page);
+                    }
+
+                    match retry_after(&page) {
+                        Some(delay) if delay > self.policy.max_delay => {
+                            println!("retry: {} returned {} and asked to wait {}s, giving up", url, page.status, delay.as_secs());
+                            return Ok(page);
+                        },
+                        Some(delay) => {
+                            println!("retry: {} returned {}", url, page.status);
+                            delay
+                        },
+                        None => {
+                            println!("retry: {} returned {}", url, page.status);
+                            self.policy.backoff(attempt)
+                        },
+                    }
+                },
+                Err(error) => {
+                    if attempt >= self.policy.max_attempts {
+                        return Err(error);
+                    }
+
+                    println!("retry: {} failed: {}", url, error);
+                    self.policy.backoff(attempt)
+                },
+            };
+
+            println!("retry: attempt {}/{} for {} in {}ms", attempt + 1, self.policy.max_attempts, url, delay.as_millis());
+            sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+/// Reads the `Retry-After` header, given either as seconds or as an HTTP date.
+fn retry_after(page: &Page) -> Option<Duration> {
+    let raw = page.headers.get_raw("Retry-After")?.first()?;
+    let value = str::from_utf8(raw).ok()?.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = value.parse::<HttpDate>().ok()?;
+    let millis = (date.0 - time::now_utc()).num_milliseconds();
+    Some(Duration::from_millis(if millis > 0 { millis as u64 } else { 0 }))
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+// Kuchiki
+use kuchiki::{NodeRef, Selectors};
+
+// Serde
+use toml;
+
+// Undup
+use undup::undup_chars;
+
+/// Profiles compiled into the binary, which `--selectors` adds to.
+const DEFAULT_PROFILES: &str = include_str!("../selectors.toml");
+
+/// The profile every other profile starts from.
+const DEFAULT_PROFILE: &str = "default";
+
+/// Which text of a matched element a field holds.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadMode {
+    /// The first text node of the element.
+    #[default]
+    Text,
+    /// All text under the element, with a line break for every `<br>` and
+    /// block element, blank lines left out.
+    Lines,
+    /// All text under the element.
+    Content,
+}
+
+/// Post-processing applied to a value read from the page.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    Trim,
+    /// Collapses runs of spaces into one.
+    Undup,
+    /// Collapses runs of any whitespace into one space and trims.
+    CollapseWhitespace,
+    StripNewlines,
+    /// Replaces line breaks with spaces.
+    JoinLines,
+    /// Removes dots, as in item numbers written "491.572.18".
+    StripDots,
+    /// Keeps the digits only.
+    Digits,
+    /// Keeps what follows the last colon, as in "Article number: 802.145.38".
+    AfterColon,
+}
+
+impl Step {
+    fn apply(self, value: &str) -> String {
+        match self {
+            Step::Trim => value.trim().to_string(),
+            Step::Undup => undup_chars(value, vec![' ']),
+            Step::CollapseWhitespace => value.split_whitespace().collect::<Vec<&str>>().join(" "),
+            Step::StripNewlines => value.replace("\n", ""),
+            Step::JoinLines => value.replace("\n", " "),
+            Step::StripDots => value.replace(".", ""),
+            Step::Digits => value.chars().filter(|c| c.is_ascii_digit()).collect(),
+            Step::AfterColon => value.rsplit(':').next().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// Where a field is found on a page and how its value is read.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Field {
+    pub selector: String,
+    /// Attribute holding the value, instead of the text.
+    #[serde(default)]
+    pub attribute: Option<String>,
+    #[serde(default)]
+    pub read: ReadMode,
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+impl Field {
+    /// The value of the field in `element`, one of the elements its selector
+    /// matched, or `None` when that is empty.
+    pub fn read(&self, element: &NodeRef) -> Option<String> {
+        let raw = match self.attribute {
+            Some(ref name) => element.as_element()?.attributes.borrow().get(name.as_str())?.to_string(),
+            None => match self.read {
+                ReadMode::Text => element.first_child()?.as_text()?.borrow().clone(),
+                ReadMode::Lines => lines(element),
+                ReadMode::Content => element.text_contents(),
+            },
+        };
+
+        let value = self.steps.iter().fold(raw, |value, step| step.apply(&value));
+        if value.is_empty() { None } else { Some(value) }
+    }
+}
+
+fn lines(element: &NodeRef) -> String {
+    let mut text = String::new();
+    for node in element.descendants() {
+        if let Some(content) = node.as_text() {
+            text.push_str(&content.borrow());
+        } else if let Some(element) = node.as_element() {
+            if ["br", "div", "p", "li", "tr"].contains(&&*element.name.local) {
+                text.push('\n');
+            }
+        }
+    }
+
+    let lines: Vec<&str> = text.lines().map(|line| line.trim()).filter(|line| !line.is_empty()).collect();
+    lines.join("\n")
+}
+
+/// The fields of one profile, by name. Fields the profile does not define
+/// match nothing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    fields: BTreeMap<String, Field>,
+}
+
+impl Profile {
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.get(name)
+    }
+
+    /// Every element under `node` matching field `name`, in page order.
+    pub fn select(&self, node: &NodeRef, name: &str) -> Vec<NodeRef> {
+        let field = match self.field(name) {
+            Some(field) => field,
+            None => return Vec::new(),
+        };
+
+        match node.select(&field.selector) {
+            Ok(css_matches) => css_matches.map(|css_match| css_match.as_node().clone()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Whether anything under `node` matches field `name`.
+    pub fn has(&self, node: &NodeRef, name: &str) -> bool {
+        !self.select(node, name).is_empty()
+    }
+
+    /// Whether `node` itself matches field `name`.
+    pub fn is(&self, node: &NodeRef, name: &str) -> bool {
+        let selectors = match self.field(name).map(|field| Selectors::compile(&field.selector)) {
+            Some(Ok(selectors)) => selectors,
+            _ => return false,
+        };
+
+        node.clone().into_element_ref().is_some_and(|element| selectors.matches(&element))
+    }
+
+    /// The value of field `name` in `element`, an element some other field
+    /// matched, or `None` when `element` does not match the selector of
+    /// `name`. Used for the attributes of links and pictures.
+    pub fn read(&self, element: &NodeRef, name: &str) -> Option<String> {
+        if !self.is(element, name) {
+            return None;
+        }
+        self.field(name)?.read(element)
+    }
+
+    /// The value of field `name` in its last match under `node`.
+    pub fn value(&self, node: &NodeRef, name: &str) -> Option<String> {
+        let field = self.field(name)?;
+        self.select(node, name).last().and_then(|element| field.read(element))
+    }
+
+    /// The values of field `name` in all its matches under `node`, leaving
+    /// out empty ones.
+    pub fn values(&self, node: &NodeRef, name: &str) -> Vec<String> {
+        let field = match self.field(name) {
+            Some(field) => field,
+            None => return Vec::new(),
+        };
+
+        self.select(node, name).iter().filter_map(|element| field.read(element)).collect()
+    }
+}
+
+/// Selector profiles by name.
+#[derive(Clone, Debug)]
+pub struct Profiles {
+    profiles: BTreeMap<String, BTreeMap<String, Field>>,
+}
+
+impl Profiles {
+    /// Profile `name`: the fields of the `default` profile, overridden by
+    /// its own.
+    pub fn get(&self, name: &str) -> Option<Profile> {
+        let own = self.profiles.get(name)?;
+
+        let mut fields = self.profiles.get(DEFAULT_PROFILE).cloned().unwrap_or_default();
+        fields.extend(own.iter().map(|(field, definition)| (field.clone(), definition.clone())));
+
+        Some(Profile { fields })
+    }
+
+    /// Adds the profiles of `other`, its fields replacing those of the same
+    /// name.
+    fn extend(&mut self, other: Profiles) {
+        for (name, fields) in other.profiles {
+            self.profiles.entry(name).or_default().extend(fields);
+        }
+    }
+}
+
+impl Default for Profile {
+    /// The built-in `default` profile.
+    fn default() -> Profile {
+        default_profiles().get(DEFAULT_PROFILE).expect("built-in selectors.toml has no default profile")
+    }
+}
+
+fn read_profiles(source: &str) -> Result<Profiles, String> {
+    let profiles: BTreeMap<String, BTreeMap<String, Field>> = toml::from_str(source).map_err(|error| error.to_string())?;
+
+    for (name, fields) in &profiles {
+        for (field, definition) in fields {
+            if Selectors::compile(&definition.selector).is_err() {
+                return Err(format!("invalid selector for {} in profile {}: {}", field, name, definition.selector));
+            }
+        }
+    }
+
+    Ok(Profiles {
+        profiles,
+    })
+}
+
+/// Returns the built-in selector profiles.
+pub fn default_profiles() -> Profiles {
+    read_profiles(DEFAULT_PROFILES).expect("built-in selectors.toml is invalid")
+}
+
+/// Parses TOML profiles made of `[profile.field]` tables on top of the
+/// built-in ones.
+pub fn parse_profiles(source: &str) -> Result<Profiles, String> {
+    let mut profiles = default_profiles();
+    profiles.extend(read_profiles(source)?);
+    Ok(profiles)
+}
+
+/// Loads profiles from a TOML file on top of the built-in ones.
+pub fn load_profiles(path: &Path) -> Result<Profiles, String> {
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut source))
+        .map_err(|error| format!("{}: {}", path.display(), error))?;
+
+    parse_profiles(&source).map_err(|error| format!("{}: {}", path.display(), error))
+}
@@ -0,0 +1,172 @@
+// Kuchiki
+use kuchiki::NodeRef;
+
+// Serde
+use serde_json;
+use serde_json::Value;
+
+/// The schema.org `Product` and `Offer` properties a product page embeds,
+/// from JSON-LD or microdata.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StructuredProduct {
+    pub name: Option<String>,
+    pub sku: Option<String>,
+    pub price: Option<f64>,
+    pub currency: Option<String>,
+    pub availability: Option<bool>,
+    pub images: Vec<String>,
+}
+
+/// Reads the first schema.org `Product` found in the JSON-LD blocks of a
+/// page, or failing that in its microdata. Properties missing from both are
+/// left empty.
+pub fn extract(document: &NodeRef) -> StructuredProduct {
+    from_json_ld(document).unwrap_or_else(|| from_microdata(document).unwrap_or_default())
+}
+
+/// Whether a schema.org availability such as "http://schema.org/InStock"
+/// means the product can be bought online.
+fn is_available(availability: &str) -> Option<bool> {
+    match availability.rsplit('/').next().unwrap_or_default() {
+        "InStock" | "OnlineOnly" | "LimitedAvailability" | "PreOrder" => Some(true),
+        "OutOfStock" | "SoldOut" | "Discontinued" | "InStoreOnly" => Some(false),
+        _ => None,
+    }
+}
+
+fn has_type(value: &Value, typ: &str) -> bool {
+    match value.get("@type") {
+        Some(Value::String(ref name)) => name == typ,
+        Some(Value::Array(ref names)) => names.iter().any(|name| name.as_str() == Some(typ)),
+        _ => false,
+    }
+}
+
+/// Finds the first object of type `typ` in a JSON-LD value, looking
+/// through arrays and `@graph`.
+fn find_typed<'a>(value: &'a Value, typ: &str) -> Option<&'a Value> {
+    match *value {
+        Value::Array(ref values) => values.iter().filter_map(|value| find_typed(value, typ)).next(),
+        Value::Object(ref object) => {
+            if has_type(value, typ) {
+                Some(value)
+            } else {
+                object.get("@graph").and_then(|graph| find_typed(graph, typ))
+            }
+        },
+        _ => None,
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    match *value {
+        Value::String(ref text) => Some(text.trim().to_string()),
+        Value::Number(ref number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match *value {
+        Value::Number(ref number) => number.as_f64(),
+        Value::String(ref text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn from_json_ld(document: &NodeRef) -> Option<StructuredProduct> {
+    let scripts = document.select("script[type=\"application/ld+json\"]").ok()?;
+
+    for script in scripts {
+        let value: Value = match serde_json::from_str(&script.text_contents()) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        let product = match find_typed(&value, "Product") {
+            Some(product) => product,
+            None => continue,
+        };
+
+        // `offers` is an Offer, an AggregateOffer or a list of either.
+        let offer = match product.get("offers") {
+            Some(Value::Array(ref offers)) => offers.first(),
+            offers => offers,
+        };
+
+        let images = match product.get("image") {
+            Some(Value::Array(ref images)) => images.iter().filter_map(image_url).collect(),
+            Some(image) => image_url(image).into_iter().collect(),
+            None => Vec::new(),
+        };
+
+        return Some(StructuredProduct {
+            name: product.get("name").and_then(text),
+            sku: product.get("sku").or_else(|| product.get("productID")).and_then(text),
+            price: offer.and_then(|offer| offer.get("price").or_else(|| offer.get("lowPrice"))).and_then(number),
+            currency: offer.and_then(|offer| offer.get("priceCurrency")).and_then(text),
+            availability: offer.and_then(|offer| offer.get("availability")).and_then(text).and_then(|availability| is_available(&availability)),
+            images,
+        });
+    }
+
+    None
+}
+
+/// An image is given as a URL or as an ImageObject.
+fn image_url(value: &Value) -> Option<String> {
+    match *value {
+        Value::Object(_) => value.get("url").or_else(|| value.get("contentUrl")).and_then(text),
+        _ => text(value),
+    }
+}
+
+fn from_microdata(document: &NodeRef) -> Option<StructuredProduct> {
+    let product = document.select("[itemscope][itemtype$=\"schema.org/Product\"]").ok()?.next()?;
+    let product = product.as_node();
+
+    let offer = item_props(product, "offers").into_iter().next();
+    let offer_prop = |name: &str| offer.as_ref().and_then(|offer| item_props(offer, name).first().and_then(item_value));
+
+    Some(StructuredProduct {
+        name: item_props(product, "name").first().and_then(item_value),
+        sku: item_props(product, "sku").first().and_then(item_value),
+        price: offer_prop("price").and_then(|price| price.parse().ok()),
+        currency: offer_prop("priceCurrency"),
+        availability: offer_prop("availability").and_then(|availability| is_available(&availability)),
+        images: item_props(product, "image").iter().filter_map(item_value).collect(),
+    })
+}
+
+/// The elements carrying property `name` of the item `scope`, leaving out
+/// those that belong to items nested inside it.
+fn item_props(scope: &NodeRef, name: &str) -> Vec<NodeRef> {
+    let css_matches = match scope.select(&format!("[itemprop~=\"{}\"]", name)) {
+        Ok(css_matches) => css_matches,
+        Err(_) => return Vec::new(),
+    };
+
+    css_matches
+        .map(|css_match| css_match.as_node().clone())
+        .filter(|node| {
+            let owner = node.ancestors().find(|ancestor| {
+                ancestor.as_element().is_some_and(|element| element.attributes.borrow().contains("itemscope"))
+            });
+            owner.as_ref() == Some(scope)
+        })
+        .collect()
+}
+
+/// The value of a microdata property: its `content`, the address it links
+/// to, or its text.
+fn item_value(node: &NodeRef) -> Option<String> {
+    let element = node.as_element()?;
+    let attributes = element.attributes.borrow();
+
+    let value = ["content", "href", "src"].iter()
+        .filter_map(|name| attributes.get(*name))
+        .next()
+        .map(|value| value.trim().to_string())
+        .unwrap_or_else(|| node.text_contents().split_whitespace().collect::<Vec<&str>>().join(" "));
+
+    if value.is_empty() { None } else { Some(value) }
+}
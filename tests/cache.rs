@@ -0,0 +1,138 @@
+extern crate hyper;
+extern crate ikea_spider_experiment;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+
+use ikea_spider_experiment::cache::{read_entry, write_entry, CachingFetcher, Entry};
+use ikea_spider_experiment::fetcher::{Error, Fetcher, Page, Result};
+
+const URL: &str = "http://www.ikea.com/sg/en";
+const ETAG: &str = "\"v1\"";
+const LAST_MODIFIED: &str = "Tue, 01 Jan 2019 00:00:00 GMT";
+
+/// Serves one page with validators, answering 304 to a request carrying its
+/// `ETag`, and keeps the headers of every request it gets.
+struct Origin {
+    requests: Mutex<Vec<Headers>>,
+}
+
+impl Origin {
+    fn new() -> Origin {
+        Origin {
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn hits(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// The value of header `name` in request `n`, counted from 0.
+    fn header(&self, n: usize, name: &str) -> Option<String> {
+        self.requests.lock().unwrap()[n].get_raw(name)
+            .and_then(|values| values.first())
+            .map(|value| String::from_utf8_lossy(value).into_owned())
+    }
+}
+
+impl Fetcher for &Origin {
+    fn fetch(&self, _url: &str, headers: &Headers) -> Result<Page> {
+        self.requests.lock().unwrap().push(headers.clone());
+
+        let mut response = Headers::new();
+        response.set_raw("ETag", vec![ETAG.as_bytes().to_vec()]);
+        response.set_raw("Last-Modified", vec![LAST_MODIFIED.as_bytes().to_vec()]);
+
+        let not_modified = headers.get_raw("If-None-Match").is_some_and(|values| values.iter().any(|value| value == ETAG.as_bytes()));
+        Ok(Page {
+            status: if not_modified { StatusCode::NotModified } else { StatusCode::Ok },
+            headers: response,
+            body: if not_modified { Vec::new() } else { b"<html>landing</html>".to_vec() },
+        })
+    }
+}
+
+fn cache_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("ikea-spider-{}-cache-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn fresh_entries_are_served_without_fetching() {
+    let origin = Origin::new();
+    let cache = CachingFetcher::new(&origin, cache_dir("fresh"), Duration::from_secs(3600), false);
+
+    let first = cache.fetch(URL, &Headers::new()).unwrap();
+    let second = cache.fetch(URL, &Headers::new()).unwrap();
+
+    assert_eq!(origin.hits(), 1);
+    assert_eq!(origin.header(0, "If-None-Match"), None);
+    assert_eq!(second.status, StatusCode::Ok);
+    assert_eq!(second.body, first.body);
+}
+
+#[test]
+fn stale_entries_are_revalidated_and_kept_on_304() {
+    let origin = Origin::new();
+    let cache = CachingFetcher::new(&origin, cache_dir("stale"), Duration::from_secs(0), false);
+
+    cache.fetch(URL, &Headers::new()).unwrap();
+    let revalidated = cache.fetch(URL, &Headers::new()).unwrap();
+
+    assert_eq!(origin.hits(), 2);
+    assert_eq!(origin.header(1, "If-None-Match"), Some(ETAG.to_string()));
+    assert_eq!(origin.header(1, "If-Modified-Since"), Some(LAST_MODIFIED.to_string()));
+    assert_eq!(revalidated.status, StatusCode::Ok);
+    assert_eq!(revalidated.body, b"<html>landing</html>".to_vec());
+}
+
+#[test]
+fn offline_mode_serves_stale_entries_and_reports_misses() {
+    let dir = cache_dir("offline");
+    let origin = Origin::new();
+    CachingFetcher::new(&origin, dir.clone(), Duration::from_secs(0), false).fetch(URL, &Headers::new()).unwrap();
+
+    let offline = CachingFetcher::new(&origin, dir, Duration::from_secs(0), true);
+    let cached = offline.fetch(URL, &Headers::new()).unwrap();
+    let missing = offline.fetch("http://www.ikea.com/my/en", &Headers::new());
+
+    assert_eq!(origin.hits(), 1);
+    assert_eq!(cached.body, b"<html>landing</html>".to_vec());
+    match missing {
+        Err(Error::Missing(url)) => assert_eq!(url, "http://www.ikea.com/my/en"),
+        _ => panic!("an offline miss should be Error::Missing"),
+    }
+}
+
+#[test]
+fn entries_written_at_once_do_not_fail_each_other() {
+    let path = cache_dir("concurrent").join("entry");
+
+    thread::scope(|scope| {
+        let writers: Vec<_> = (0..8).map(|_| scope.spawn(|| {
+            let entry = Entry {
+                url: URL.to_string(),
+                fetched_at: 0,
+                page: Page { status: StatusCode::Ok, headers: Headers::new(), body: b"<html>landing</html>".to_vec() },
+            };
+            write_entry(&path, &entry)
+        })).collect();
+
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+    });
+
+    assert_eq!(read_entry(&path).unwrap().page.body, b"<html>landing</html>");
+    assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+}
@@ -0,0 +1,94 @@
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::process::Command;
+
+const HEADER: &str = "Item Number,Name,Type,Price,Unit,Metric,Image URL,URL,Department,Category,Subcategory,Department URL,Category URL, Subcategory URL\n";
+
+fn output_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("ikea-spider-{}-{}", process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_file(path: &Path, contents: &str) {
+    File::create(path).unwrap().write_all(contents.as_bytes()).unwrap();
+}
+
+fn read_file(path: &Path) -> String {
+    let mut contents = String::new();
+    File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+    contents
+}
+
+fn run_compare(dir: &Path, countries: &str) -> String {
+    let result = Command::new(env!("CARGO_BIN_EXE_ikea-spider-experiment"))
+        .args(["-t", "compare", "-c", countries])
+        .arg("--input").arg(dir.join("output-{country}.csv"))
+        .arg("--rates").arg(dir.join("rates.toml"))
+        .arg("-o").arg(dir.join("comparison.csv"))
+        .output()
+        .unwrap();
+
+    assert!(result.status.success());
+    String::from_utf8(result.stdout).unwrap()
+}
+
+#[test]
+fn products_are_joined_by_item_number_across_markets() {
+    let dir = output_dir("compare");
+
+    write_file(&dir.join("output-sg-en.csv"), &format!("{}{}{}", HEADER,
+        "\"49157218\",\"MALM\",\"Bed frame, high\",\"$1,299.00\",\"/ piece\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\"\n",
+        "\"19011539\",\"HEMNES \"\"day-bed\"\"\",\"Day-bed frame\",\"$399.00\",\"/ piece\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\"\n"));
+    write_file(&dir.join("output-th-th.csv"), &format!("{}{}{}", HEADER,
+        "\"49157218\",\"มาล์ม\",\"โครงเตียง\",\"฿12,990\",\"/ ชิ้น\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\"\n",
+        "\"80263844\",\"LACK\",\"Side table\",\"฿299\",\"/ ชิ้น\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\"\n"));
+    write_file(&dir.join("rates.toml"), "base = \"SGD\"\n\n[rates]\nSGD = 1.0\nTHB = 0.04\n");
+
+    run_compare(&dir, "sg-en,th-th");
+
+    let comparison = read_file(&dir.join("comparison.csv"));
+    let lines: Vec<&str> = comparison.lines().collect();
+    assert_eq!(lines, vec![
+        "\"Item Number\",\"sg-en Name\",\"sg-en Price\",\"sg-en Price (SGD)\",\"th-th Name\",\"th-th Price\",\"th-th Price (SGD)\"",
+        "\"19011539\",\"HEMNES \"\"day-bed\"\"\",\"$399.00\",\"399.00\",\"\",\"\",\"\"",
+        "\"49157218\",\"MALM\",\"$1,299.00\",\"1299.00\",\"มาล์ม\",\"฿12,990\",\"519.60\"",
+        "\"80263844\",\"\",\"\",\"\",\"LACK\",\"฿299\",\"11.96\"",
+    ]);
+}
+
+#[test]
+fn missing_rates_leave_converted_prices_empty() {
+    let dir = output_dir("compare-rates");
+
+    write_file(&dir.join("output-my-en.csv"), &format!("{}{}", HEADER,
+        "\"49157218\",\"MALM\",\"Bed frame, high\",\"RM 1.299,00\",\"/ piece\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\"\n"));
+    write_file(&dir.join("rates.toml"), "base = \"SGD\"\n\n[rates]\nSGD = 1.0\n");
+
+    run_compare(&dir, "my-en");
+
+    let comparison = read_file(&dir.join("comparison.csv"));
+    assert!(comparison.contains("\"49157218\",\"MALM\",\"RM 1.299,00\",\"\"\n"));
+}
+
+#[test]
+fn price_amounts_are_read_when_the_displayed_price_is_empty() {
+    let dir = output_dir("compare-amounts");
+    let header = "Item Number,Name,Type,Price,Unit,Metric,Image URL,URL,Department,Category,Subcategory,Department URL,Category URL, Subcategory URL,Price Amount,Price Currency\n";
+
+    write_file(&dir.join("output-sg-en.csv"), &format!("{}{}", header,
+        "\"79157219\",\"MALM\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"319.00\",\"SGD\"\n"));
+    write_file(&dir.join("output-my-en.csv"), &format!("{}{}", header,
+        "\"79157219\",\"MALM\",\"\",\"RM 1,000.00\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"1000.00\",\"THB\"\n"));
+    write_file(&dir.join("rates.toml"), "base = \"SGD\"\n\n[rates]\nSGD = 1.0\nMYR = 0.31\nTHB = 0.04\n");
+
+    run_compare(&dir, "sg-en,my-en");
+
+    let comparison = read_file(&dir.join("comparison.csv"));
+    // The row's currency wins over the market's.
+    assert!(comparison.contains("\"79157219\",\"MALM\",\"319.00 SGD\",\"319.00\",\"MALM\",\"RM 1,000.00\",\"40.00\"\n"));
+}
@@ -0,0 +1,106 @@
+extern crate ikea_spider_experiment;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use ikea_spider_experiment::country::{default_countries, find_country, load_countries};
+use ikea_spider_experiment::dimensions::Labels;
+
+fn catalogue_file(name: &str, contents: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("ikea-spider-{}-countries", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn toml_catalogues_fill_in_defaults() {
+    let path = catalogue_file("countries.toml", r#"
+        [[country]]
+        name = "Singapore"
+        code = "sg-en"
+        language = "en"
+        path = "/sg/en"
+        currency = "SGD"
+        locale = "en_SG"
+
+        [[country]]
+        name = "Malaysia Bahasa"
+        code = "my-ms"
+        language = "ms"
+        path = "/my/ms"
+        currency = "MYR"
+        locale = "ms_MY"
+        decimal = ","
+        grouping = "."
+        selectors = "redesign"
+
+        [country.dimension_labels]
+        width = ["Lebar"]
+    "#);
+
+    let countries = load_countries(&path).unwrap();
+    assert_eq!(countries.len(), 2);
+
+    let singapore = find_country(&countries, "sg-en").unwrap();
+    assert_eq!(singapore.name, "Singapore");
+    assert_eq!(singapore.path, "/sg/en");
+    assert_eq!(singapore.currency, "SGD");
+    assert_eq!(singapore.decimal, '.');
+    assert_eq!(singapore.grouping, ',');
+    assert_eq!(singapore.selectors, "default");
+    assert_eq!(singapore.dimension_labels, Labels::default());
+
+    let malaysia = find_country(&countries, "my-ms").unwrap();
+    assert_eq!(malaysia.decimal, ',');
+    assert_eq!(malaysia.grouping, '.');
+    assert_eq!(malaysia.selectors, "redesign");
+    assert_eq!(malaysia.dimension_labels.width, vec!["Lebar".to_string()]);
+    assert_eq!(malaysia.dimension_labels.height, Labels::default().height);
+}
+
+#[test]
+fn json_catalogues_have_the_same_shape() {
+    let path = catalogue_file("countries.json", r#"{
+        "country": [
+            {
+                "name": "Thailand Thai",
+                "code": "th-th",
+                "language": "th",
+                "path": "/th/th",
+                "currency": "THB",
+                "locale": "th_TH",
+                "dimension_labels": {"width": ["กว้าง"]}
+            }
+        ]
+    }"#);
+
+    let countries = load_countries(&path).unwrap();
+    assert_eq!(countries.len(), 1);
+    assert_eq!(countries[0].code, "th-th");
+    assert_eq!(countries[0].currency, "THB");
+    assert_eq!(countries[0].decimal, '.');
+    assert_eq!(countries[0].selectors, "default");
+    assert_eq!(countries[0].dimension_labels.width, vec!["กว้าง".to_string()]);
+}
+
+#[test]
+fn broken_catalogues_are_reported_with_their_path() {
+    let path = catalogue_file("broken.toml", "[[country]]\nname = \"Nowhere\"\n");
+    let error = load_countries(&path).unwrap_err();
+    assert!(error.starts_with(&path.display().to_string()));
+
+    let missing = catalogue_file("unused", "").with_file_name("missing.toml");
+    assert!(load_countries(&missing).is_err());
+}
+
+#[test]
+fn the_built_in_catalogue_has_unique_codes() {
+    let countries = default_countries();
+    for country in &countries {
+        assert_eq!(countries.iter().filter(|other| other.code == country.code).count(), 1);
+    }
+}
@@ -0,0 +1,69 @@
+extern crate ikea_spider_experiment;
+
+use ikea_spider_experiment::country::{default_countries, find_country, Country};
+use ikea_spider_experiment::dimensions::{parse_dimensions, Dimensions};
+
+fn country(code: &str) -> Country {
+    find_country(&default_countries(), code).unwrap().clone()
+}
+
+#[test]
+fn measurements_are_normalised_to_metric_units() {
+    let text = "Width: 35 \"\nDepth: 450 mm\nMax. height: 1.2 m\nWeight: 2 lb\nVolume: 750 ml";
+
+    assert_eq!(parse_dimensions(text, &country("sg-en")), Dimensions {
+        width: Some(88.9),
+        depth: Some(45.0),
+        height: Some(120.0),
+        length: None,
+        weight: Some(0.91),
+        volume: Some(0.75),
+    });
+}
+
+#[test]
+fn the_first_of_several_labels_wins() {
+    let dimensions = parse_dimensions("Min. height: 70 cm\nMax. height: 110 cm", &country("sg-en"));
+
+    assert_eq!(dimensions.height, Some(70.0));
+}
+
+#[test]
+fn decimals_follow_the_country() {
+    assert_eq!(parse_dimensions("Lebar: 12,5 cm", &country("my-ms")).width, Some(12.5));
+    assert_eq!(parse_dimensions("Width: 1,200 mm", &country("sg-en")).width, Some(120.0));
+}
+
+#[test]
+fn only_whole_labels_count() {
+    let dimensions = parse_dimensions("Mattress length: 200 cm\nLength: 209 cm\nPackage weight: 45.5 kg\nBreite / width: 12 cm", &country("sg-en"));
+
+    assert_eq!(dimensions, Dimensions { length: Some(209.0), ..Dimensions::default() });
+}
+
+#[test]
+fn labels_follow_the_country() {
+    assert_eq!(parse_dimensions("Lebar: 90 cm\nBerat: 12,5 kg", &country("my-ms")), Dimensions {
+        width: Some(90.0),
+        weight: Some(12.5),
+        ..Dimensions::default()
+    });
+    assert_eq!(parse_dimensions("Lebar: 90 sm\nIsipadu: 1,5 liter", &country("my-ms")), Dimensions {
+        width: Some(90.0),
+        volume: Some(1.5),
+        ..Dimensions::default()
+    });
+    assert_eq!(parse_dimensions("ความกว้าง: 90 ซม.\nความสูง: 1.2 m\nน้ำหนัก: 2,500 กรัม\nปริมาตร: 500 มล.", &country("th-th")), Dimensions {
+        width: Some(90.0),
+        height: Some(120.0),
+        weight: Some(2.5),
+        volume: Some(0.5),
+        ..Dimensions::default()
+    });
+    assert_eq!(parse_dimensions("Width: 90 cm", &country("th-th")).width, None);
+}
+
+#[test]
+fn unknown_units_and_free_text_are_ignored() {
+    assert_eq!(parse_dimensions("180x200 cm\nWidth: 3 cubits", &country("sg-en")), Dimensions::default());
+}
@@ -0,0 +1,357 @@
+extern crate hyper;
+extern crate ikea_spider_experiment;
+extern crate url;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+
+use url::Url;
+
+use ikea_spider_experiment::{fetch_departments, fetch_product_info, fetch_products_from_all_departments, Country, Department, Image, Product, Site};
+use ikea_spider_experiment::country::{default_countries, find_country};
+use ikea_spider_experiment::dimensions::{Dimensions, Package};
+use ikea_spider_experiment::fetcher::{Error, Fetcher, Page, Result};
+use ikea_spider_experiment::selectors::parse_profiles;
+
+/// Serves checked-in HTML from `tests/fixtures` for a fixed set of URLs.
+struct FixtureFetcher {
+    pages: BTreeMap<&'static str, &'static str>,
+}
+
+impl FixtureFetcher {
+    fn new() -> FixtureFetcher {
+        let mut pages = BTreeMap::new();
+        pages.insert("http://www.ikea.com/sg/en", "department_landing.html");
+        pages.insert("http://www.ikea.com/sg/en/catalog/departments/bedroom/", "visual_nav.html");
+        pages.insert("http://www.ikea.com/sg/en/catalog/categories/departments/bedroom/16284/", "product_list.html");
+        pages.insert("http://www.ikea.com/sg/en/catalog/products/S49157218/", "product_detail.html");
+        pages.insert("http://www.ikea.com/sg/en/catalog/products/S79157219/", "product_json_ld.html");
+        pages.insert("http://www.ikea.com/sg/en/catalog/products/S69011540/", "product_microdata.html");
+        pages.insert("http://www.ikea.com/sg/en/catalog/products/S59198833/", "product_in_store_only.html");
+
+        FixtureFetcher {
+            pages,
+        }
+    }
+}
+
+impl Fetcher for FixtureFetcher {
+    fn fetch(&self, url: &str, _headers: &Headers) -> Result<Page> {
+        let name = match self.pages.get(url) {
+            Some(name) => name,
+            None => return Err(Error::Missing(url.to_string())),
+        };
+
+        let mut body = Vec::new();
+        File::open(fixture_path(name))?.read_to_end(&mut body)?;
+
+        Ok(Page {
+            status: StatusCode::Ok,
+            headers: Headers::new(),
+            body,
+        })
+    }
+}
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+fn site<'a>(fetcher: &'a FixtureFetcher) -> Site<'a> {
+    Site::new(fetcher, Url::parse("http://www.ikea.com").unwrap())
+}
+
+fn singapore() -> Country {
+    find_country(&default_countries(), "sg-en").unwrap().clone()
+}
+
+fn bedroom() -> Department {
+    Department {
+        name: "Bedroom".to_string(),
+        url: "/sg/en/catalog/departments/bedroom/".to_string(),
+    }
+}
+
+fn crawl_bedroom(fetcher: &FixtureFetcher, error_str: &mut String) -> BTreeMap<String, Product> {
+    let mut m = BTreeMap::new();
+    let mut visited_urls = BTreeMap::new();
+    fetch_products_from_all_departments(&site(fetcher), &mut visited_urls, &mut m, vec![bedroom()], error_str);
+    m
+}
+
+#[test]
+fn departments_are_read_from_landing_page() {
+    let departments = fetch_departments(&site(&FixtureFetcher::new()), &singapore()).unwrap();
+
+    let found: Vec<(&str, &str)> = departments.iter().map(|d| (d.name.as_str(), d.url.as_str())).collect();
+    assert_eq!(found, vec![
+        ("Bedroom", "/sg/en/catalog/departments/bedroom/"),
+        ("Living room", "/sg/en/catalog/departments/living_room/"),
+        ("Kitchen", "/sg/en/catalog/departments/kitchen/"),
+        ("Outdoor", "/sg/en/catalog/departments/outdoor/"),
+        ("IKEA Family", "http://family.ikea.com/sg/en/"),
+    ]);
+}
+
+/// Serves the fixtures as if the site were mirrored under `/ikea-mirror/`.
+struct MirroredFixtures(FixtureFetcher);
+
+impl Fetcher for MirroredFixtures {
+    fn fetch(&self, url: &str, headers: &Headers) -> Result<Page> {
+        match url.strip_prefix("http://mirror.test/ikea-mirror/") {
+            Some(path) => self.0.fetch(&format!("http://www.ikea.com/{}", path), headers),
+            None => Err(Error::Missing(url.to_string())),
+        }
+    }
+}
+
+#[test]
+fn a_base_url_with_a_path_holds_the_site() {
+    let fetcher = MirroredFixtures(FixtureFetcher::new());
+    let site = Site::new(&fetcher, Url::parse("http://mirror.test/ikea-mirror").unwrap());
+
+    assert_eq!(site.address("/sg/en"), "http://mirror.test/ikea-mirror/sg/en");
+    assert_eq!(site.link("http://mirror.test/ikea-mirror/sg/en/", "/other/"), Some("http://mirror.test/other/".to_string()));
+    assert_eq!(site.link("http://mirror.test/ikea-mirror/sg/en/", "mailto:info@ikea.com"), None);
+    assert_eq!(site.link("http://mirror.test/ikea-mirror/sg/en/", "javascript:void(0)"), None);
+
+    let mut error_str = String::new();
+    let product = fetch_product_info(&site, "/sg/en/catalog/products/S49157218/", &singapore(), &mut error_str).unwrap();
+    assert_eq!(product.id, "S49157218");
+    // "../S79157219/" stays on the mirrored site.
+    assert!(product.variant_urls.contains(&"/sg/en/catalog/products/S79157219/".to_string()));
+}
+
+#[test]
+fn departments_are_none_when_landing_page_is_missing() {
+    let mut country = singapore();
+    country.path = "/xx/xx".to_string();
+    assert!(fetch_departments(&site(&FixtureFetcher::new()), &country).is_none());
+}
+
+#[test]
+fn products_are_collected_through_visual_navigation() {
+    let mut error_str = String::new();
+    let m = crawl_bedroom(&FixtureFetcher::new(), &mut error_str);
+
+    let urls: Vec<&str> = m.keys().map(|url| url.as_str()).collect();
+    assert_eq!(urls, vec![
+        "/sg/en/catalog/products/S19011539/",
+        "/sg/en/catalog/products/S49157218/",
+    ]);
+
+    let malm = &m["/sg/en/catalog/products/S49157218/"];
+    assert_eq!(malm.url, "/sg/en/catalog/products/S49157218/");
+    assert_eq!(malm.department, "Bedroom");
+    assert_eq!(malm.department_url, "/sg/en/catalog/departments/bedroom/");
+    assert_eq!(malm.category, "Beds");
+    assert_eq!(malm.category_url, "/sg/en/catalog/categories/departments/bedroom/16284/");
+    assert_eq!(malm.subcategory, "");
+    assert_eq!(malm.subcategory_url, "");
+    assert!(malm.is_new);
+    assert!(!malm.last_chance);
+    assert!(!m["/sg/en/catalog/products/S19011539/"].is_new);
+}
+
+#[test]
+fn missing_category_pages_are_reported() {
+    let mut error_str = String::new();
+    crawl_bedroom(&FixtureFetcher::new(), &mut error_str);
+
+    assert_eq!(error_str, "Failed to fetch HTML at http://www.ikea.com/sg/en/catalog/categories/departments/bedroom/19053/\n");
+}
+
+#[test]
+fn product_info_is_read_from_product_page() {
+    let mut error_str = String::new();
+    let product = fetch_product_info(&site(&FixtureFetcher::new()), "/sg/en/catalog/products/S49157218/", &singapore(), &mut error_str).unwrap();
+
+    assert_eq!(error_str, "");
+    assert_eq!(product.id, "S49157218");
+    assert_eq!(product.name, "MALM bed frame");
+    assert_eq!(product.typ, "Bed frame, high");
+    assert_eq!(product.country, "Singapore");
+    assert_eq!(product.price, "$299.00");
+    assert_eq!(product.price_amount, Some(299.0));
+    assert_eq!(product.price_currency, "SGD");
+    assert_eq!(product.regular_price, Some(349.0));
+    assert_eq!(product.discounted_price, Some(299.0));
+    assert_eq!(product.family_price, Some(279.0));
+    assert_eq!(product.offer_valid_from, "2019-01-01");
+    assert_eq!(product.offer_valid_to, "2019-01-31");
+    assert_eq!(product.unit, "/ piece");
+    assert_eq!(product.metric, "180x200 cm");
+    assert_eq!(product.dimensions, Dimensions {
+        width: Some(196.0),
+        depth: None,
+        height: Some(100.0),
+        length: Some(209.0),
+        // "Package weight" is the package's, read with the packages below.
+        weight: None,
+        volume: None,
+    });
+    assert_eq!(product.image_url, "/PIAimages/0384394_PE557222_S4.JPG");
+    assert_eq!(product.images, vec![
+        Image {
+            url: "/PIAimages/0384394_PE557222_S4.JPG".to_string(),
+            large: "/PIAimages/0384394_PE557222_S5.JPG".to_string(),
+            zoom: "/PIAimages/0384394_PE557222.JPG".to_string(),
+            path: "".to_string(),
+            hash: "".to_string(),
+        },
+        Image {
+            url: "/PIAimages/0384395_PE557223_S4.JPG".to_string(),
+            large: "/PIAimages/0384395_PE557223_S5.JPG".to_string(),
+            zoom: "/PIAimages/0384395_PE557223.JPG".to_string(),
+            path: "".to_string(),
+            hash: "".to_string(),
+        },
+        Image {
+            url: "/PIAimages/0447234_PE597354_S4.JPG".to_string(),
+            large: "/PIAimages/0447234_PE597354_S5.JPG".to_string(),
+            zoom: "".to_string(),
+            path: "".to_string(),
+            hash: "".to_string(),
+        },
+    ]);
+    assert_eq!(product.url, "/sg/en/catalog/products/S49157218/");
+    assert_eq!(product.variant_urls, vec!["/sg/en/catalog/products/S79157219/"]);
+    assert_eq!(product.rating, Some(4.6));
+    assert_eq!(product.review_count, Some(1204));
+    assert_eq!(product.buy_online, Some(true));
+    assert_eq!(product.stock_hint, "In stock at IKEA Tampines");
+    assert!(!product.is_new);
+    assert!(product.last_chance);
+    assert_eq!(product.description, "A clean design that's just as beautiful on all sides.\n\
+                                     Place the bed freestanding or with the headboard against a wall.");
+    assert_eq!(product.key_features, vec![
+        "Adjustable bed sides allow you to use mattresses of different thicknesses.",
+        "Real wood veneer makes this bed frame durable.",
+    ]);
+    assert_eq!(product.materials, "Headboard: Particleboard, Fibreboard, Oak veneer\nBed side: Particleboard, Fibreboard");
+    assert_eq!(product.care_instructions, "Wipe clean with a cloth dampened in a mild cleaner.");
+    assert_eq!(product.designer, "IKEA of Sweden, Nike Karlsson");
+    assert_eq!(product.good_to_know, "Mattress and bedding are sold separately.");
+    let documents: Vec<(&str, &str, &str)> = product.documents.iter()
+        .map(|document| (document.url.as_str(), document.kind.as_str(), document.language.as_str()))
+        .collect();
+    assert_eq!(documents, vec![
+        ("/sg/en/assembly_instructions/malm-bed-frame-high__AA-75286-15_pub.pdf", "assembly", "en"),
+        ("/manuals/malm__AA-1.pdf", "manual", "ms"),
+        ("/sg/en/catalog/docs/malm-product-info.pdf", "product-info", "en"),
+    ]);
+    assert_eq!(product.packages, vec![
+        Package {
+            item_number: "80214538".to_string(),
+            quantity: 1,
+            dimensions: Dimensions { width: Some(62.0), height: Some(10.0), length: Some(213.0), weight: Some(29.5), ..Dimensions::default() },
+        },
+        Package {
+            item_number: "20249487".to_string(),
+            quantity: 2,
+            dimensions: Dimensions { width: Some(8.0), height: Some(6.0), length: Some(182.0), weight: Some(3.35), ..Dimensions::default() },
+        },
+    ]);
+}
+
+#[test]
+fn json_ld_is_preferred_to_selectors() {
+    let mut error_str = String::new();
+    let product = fetch_product_info(&site(&FixtureFetcher::new()), "/sg/en/catalog/products/S79157219/", &singapore(), &mut error_str).unwrap();
+
+    assert_eq!(error_str, "");
+    assert_eq!(product.id, "S79157219");
+    assert_eq!(product.name, "MALM Bed frame, high, white");
+    assert_eq!(product.price_amount, Some(319.0));
+    assert_eq!(product.price_currency, "SGD");
+    // The buy button on the page outranks the OutOfStock offer.
+    assert_eq!(product.buy_online, Some(true));
+    assert_eq!(product.image_url, "https://www.ikea.com/sg/en/images/products/malm-bed-frame-high-white.JPG");
+    let images: Vec<&str> = product.images.iter().map(|image| image.url.as_str()).collect();
+    assert_eq!(images, vec![
+        "https://www.ikea.com/sg/en/images/products/malm-bed-frame-high-white.JPG",
+        "https://www.ikea.com/sg/en/images/products/malm-bed-frame-high-white-2.JPG",
+    ]);
+}
+
+#[test]
+fn microdata_is_read_when_there_is_no_json_ld() {
+    let mut error_str = String::new();
+    let product = fetch_product_info(&site(&FixtureFetcher::new()), "/sg/en/catalog/products/S69011540/", &singapore(), &mut error_str).unwrap();
+
+    assert_eq!(error_str, "");
+    assert_eq!(product.id, "19011539");
+    assert_eq!(product.name, "HEMNES Day-bed frame with 3 drawers");
+    assert_eq!(product.price, "$399.00");
+    assert_eq!(product.price_amount, Some(399.0));
+    assert_eq!(product.price_currency, "SGD");
+    assert_eq!(product.buy_online, Some(true));
+    assert_eq!(product.image_url, "/PIAimages/0158855_PE316774_S4.JPG");
+}
+
+#[test]
+fn products_in_store_only_are_not_bought_online() {
+    let mut error_str = String::new();
+    let product = fetch_product_info(&site(&FixtureFetcher::new()), "/sg/en/catalog/products/S59198833/", &singapore(), &mut error_str).unwrap();
+
+    assert_eq!(error_str, "");
+    assert_eq!(product.id, "S59198833");
+    assert_eq!(product.price_amount, Some(899.0));
+    assert_eq!(product.buy_online, Some(false));
+}
+
+#[test]
+fn selector_profiles_change_where_fields_are_read() {
+    let profiles = parse_profiles(r##"
+        [redesign.price]
+        selector = ".familyPriceContainer .familyPrice"
+        steps = ["trim"]
+
+        [redesign.item_number]
+        selector = "#itemNumber"
+        steps = ["trim"]
+
+        # Attributes are config too: the large rendition moved to data-zoom,
+        # and document languages are no longer marked.
+        [redesign.gallery_large]
+        selector = "[data-zoom]"
+        attribute = "data-zoom"
+
+        [redesign.document_language]
+        selector = "[data-lang]"
+        attribute = "data-lang"
+    "##).unwrap();
+
+    let mut country = singapore();
+    country.selectors = "redesign".to_string();
+
+    let fetcher = FixtureFetcher::new();
+    let mut site = site(&fetcher);
+    site.selectors = profiles.get(&country.selectors).unwrap();
+
+    let mut error_str = String::new();
+    let product = fetch_product_info(&site, "/sg/en/catalog/products/S49157218/", &country, &mut error_str).unwrap();
+
+    assert_eq!(product.price, "$279.00");
+    assert_eq!(product.id, "S491.572.18");
+    assert_eq!(product.images[0].large, "/PIAimages/0384394_PE557222.JPG");
+    assert_eq!(product.images[2].large, "");
+    assert!(product.documents.iter().all(|document| document.language == "en"));
+    // Fields the profile leaves out come from the default profile.
+    assert_eq!(product.name, "MALM bed frame");
+    assert_eq!(product.unit, "/ piece");
+}
+
+#[test]
+fn missing_product_pages_are_reported() {
+    let mut error_str = String::new();
+    let product = fetch_product_info(&site(&FixtureFetcher::new()), "/sg/en/catalog/products/S19011539/", &singapore(), &mut error_str);
+
+    assert!(product.is_none());
+    assert_eq!(error_str, "Failed to fetch product data at http://www.ikea.com/sg/en/catalog/products/S19011539/\n");
+}
@@ -0,0 +1,99 @@
+extern crate hyper;
+extern crate ikea_spider_experiment;
+extern crate url;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+
+use url::Url;
+
+use ikea_spider_experiment::{Image, Product, Site};
+use ikea_spider_experiment::country::{default_countries, find_country};
+use ikea_spider_experiment::fetcher::{Fetcher, Page, Result};
+use ikea_spider_experiment::mirror::Mirror;
+
+/// Answers every URL with its own path as the body.
+struct Echo;
+
+impl Fetcher for Echo {
+    fn fetch(&self, url: &str, _headers: &Headers) -> Result<Page> {
+        Ok(Page {
+            status: StatusCode::Ok,
+            headers: Headers::new(),
+            body: Url::parse(url).unwrap().path().as_bytes().to_vec(),
+        })
+    }
+}
+
+fn mirror_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("ikea-spider-{}-mirror-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn product(id: &str, url: &str, image: &str) -> Product {
+    Product {
+        id: id.to_string(),
+        url: url.to_string(),
+        images: vec![Image { url: image.to_string(), large: String::new(), zoom: String::new(), path: String::new(), hash: String::new() }],
+        ..Product::default()
+    }
+}
+
+#[test]
+fn products_without_a_safe_item_number_do_not_share_or_escape_a_directory() {
+    let dir = mirror_dir("paths");
+    let mirror = Mirror::new(dir.clone()).unwrap();
+    let site = Site::new(&Echo, Url::parse("http://www.ikea.com").unwrap());
+    let country = find_country(&default_countries(), "sg-en").unwrap().clone();
+
+    let mut products = vec![
+        product("", "/sg/en/products/S1/", "/a.jpg"),
+        product("", "/sg/en/products/S2/", "/b.jpg"),
+        product("../../S3/x", "/sg/en/products/S3/", "/c.jpg"),
+    ];
+    let mut error_str = String::new();
+    for product in &mut products {
+        mirror.download_images(&site, &country, product, &mut error_str);
+    }
+
+    let paths: Vec<&str> = products.iter().map(|product| product.images[0].path.as_str()).collect();
+    assert_eq!(error_str, "");
+    assert!(paths[0].starts_with("sg-en/url-") && paths[1].starts_with("sg-en/url-"));
+    assert_ne!(paths[0], paths[1]);
+    assert_eq!(paths[2], "sg-en/------S3-x/1.jpg");
+    assert_eq!(fs::read_to_string(dir.join(paths[0])).unwrap(), "/a.jpg");
+    assert_eq!(fs::read_to_string(dir.join(paths[1])).unwrap(), "/b.jpg");
+}
+
+#[test]
+fn products_sharing_a_picture_can_be_mirrored_at_once() {
+    let dir = mirror_dir("concurrent");
+    let mirror = Mirror::new(dir.clone()).unwrap();
+    let site = Site::new(&Echo, Url::parse("http://www.ikea.com").unwrap());
+    let country = find_country(&default_countries(), "sg-en").unwrap().clone();
+
+    let errors: Vec<String> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..8).map(|n| {
+            let (mirror, site, country) = (&mirror, &site, &country);
+            scope.spawn(move || {
+                let mut product = product(&format!("{}", n), &format!("/sg/en/products/S{}/", n), "/shared.jpg");
+                let mut error_str = String::new();
+                mirror.download_images(site, country, &mut product, &mut error_str);
+                error_str
+            })
+        }).collect();
+
+        workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+    });
+
+    assert!(errors.iter().all(|error_str| error_str.is_empty()), "{:?}", errors);
+    assert_eq!(fs::read_dir(dir.join("objects")).unwrap().count(), 1);
+    assert_eq!(fs::read_to_string(dir.join("sg-en/7/1.jpg")).unwrap(), "/shared.jpg");
+}
@@ -0,0 +1,341 @@
+extern crate hyper;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use hyper::server::{Listening, Request, Response, Server};
+use hyper::status::StatusCode;
+use hyper::uri::RequestUri;
+
+const IMAGE_ETAG: &[u8] = b"\"v1\"";
+
+/// A small synthetic IKEA-shaped site served from a local port.
+///
+/// On the Singapore site Kitchen answers 500 and Gone is a broken link;
+/// Bedroom nests Beds > Double beds and Wardrobes, whose product list holds
+/// a product page that answers 500 and a broken product link. MALM links a
+/// white variant that no product list reaches. The Malaysia
+/// site has a single product. Every product image has the same content and
+/// answers 304 to a request carrying its `ETag`. Every product links its
+/// assembly instructions.
+struct MockSite {
+    listening: Listening,
+    hits: Arc<Mutex<BTreeMap<String, usize>>>,
+}
+
+impl MockSite {
+    fn start() -> MockSite {
+        let hits = Arc::new(Mutex::new(BTreeMap::new()));
+        let counter = hits.clone();
+
+        let mut server = Server::http("127.0.0.1:0").unwrap();
+        // A kept-alive connection ties up a server thread until it times out.
+        server.keep_alive(None);
+        let listening = server.handle(move |req: Request, mut res: Response| {
+            let path = match req.uri {
+                RequestUri::AbsolutePath(ref path) => path.clone(),
+                _ => String::new(),
+            };
+            *counter.lock().unwrap().entry(path.clone()).or_insert(0) += 1;
+
+            if path.starts_with("/PIAimages/") {
+                res.headers_mut().set_raw("ETag", vec![IMAGE_ETAG.to_vec()]);
+                if req.headers.get_raw("If-None-Match").is_some_and(|values| values.iter().any(|value| value == IMAGE_ETAG)) {
+                    *res.status_mut() = StatusCode::NotModified;
+                    res.send(b"").unwrap();
+                    return;
+                }
+            }
+
+            let (status, body) = page(&path);
+            *res.status_mut() = status;
+            res.send(body.as_bytes()).unwrap();
+        }).unwrap();
+
+        MockSite {
+            listening,
+            hits,
+        }
+    }
+
+    fn base_address(&self) -> String {
+        format!("http://{}", self.listening.socket)
+    }
+
+    fn hits(&self, path: &str) -> usize {
+        self.hits.lock().unwrap().get(path).cloned().unwrap_or(0)
+    }
+}
+
+impl Drop for MockSite {
+    fn drop(&mut self) {
+        // Listening joins its acceptor thread on drop, which never returns.
+        let _ = self.listening.close();
+    }
+}
+
+fn page(path: &str) -> (StatusCode, String) {
+    match path {
+        "/sg/en" => (StatusCode::Ok, departments(&[
+            ("/sg/en/kitchen/", "Kitchen"),
+            ("/sg/en/gone/", "Gone"),
+            ("/sg/en/bedroom/", "Bedroom"),
+        ])),
+        "/sg/en/bedroom/" => (StatusCode::Ok, categories(&[
+            ("/sg/en/bedroom/beds/", "Beds"),
+            ("/sg/en/bedroom/wardrobes/", "Wardrobes"),
+        ])),
+        "/sg/en/bedroom/beds/" => (StatusCode::Ok, categories(&[
+            ("/sg/en/bedroom/beds/double/", "Double beds"),
+        ])),
+        "/sg/en/bedroom/beds/double/" => (StatusCode::Ok, products(&[
+            "/sg/en/products/S49157218/",
+            "/sg/en/products/S19011539/",
+        ])),
+        "/sg/en/bedroom/wardrobes/" => (StatusCode::Ok, products(&[
+            "/sg/en/products/S29018576/",
+            "/sg/en/products/S00000000/",
+        ])),
+        "/my/en" => (StatusCode::Ok, departments(&[
+            ("/my/en/bedroom/", "Bedroom"),
+        ])),
+        "/my/en/bedroom/" => (StatusCode::Ok, products(&[
+            "/my/en/products/S49157218/",
+        ])),
+        "/sg/en/products/S49157218/" => (StatusCode::Ok, product("491.572.18", "MALM", "Bed frame, high", "$299.00", &[
+            "/sg/en/products/S49157218/",
+            "/sg/en/products/S79157219/",
+        ])),
+        "/sg/en/products/S79157219/" => (StatusCode::Ok, product("791.572.19", "MALM", "Bed frame, high, white", "$299.00", &[
+            "/sg/en/products/S49157218/",
+            "/sg/en/products/S79157219/",
+        ])),
+        "/my/en/products/S49157218/" => (StatusCode::Ok, product("491.572.18", "MALM", "Bed frame, high", "$299.00", &[])),
+        "/sg/en/products/S19011539/" => (StatusCode::Ok, product("190.115.39", "HEMNES", "Day-bed frame", "$399.00", &[])),
+        "/manuals/491.572.18.pdf" | "/manuals/791.572.19.pdf" | "/manuals/190.115.39.pdf" => (StatusCode::Ok, "PDF".to_string()),
+        "/PIAimages/491.572.18.JPG" | "/PIAimages/791.572.19.JPG" | "/PIAimages/190.115.39.JPG" => (StatusCode::Ok, "JPEG".to_string()),
+        "/sg/en/kitchen/" | "/sg/en/products/S29018576/" => (StatusCode::InternalServerError, "Internal Server Error".to_string()),
+        _ => (StatusCode::NotFound, "Not Found".to_string()),
+    }
+}
+
+fn departments(links: &[(&str, &str)]) -> String {
+    let mut html = String::from("<html><body>");
+    for &(url, name) in links {
+        html.push_str(&format!("<div class=\"departmentLinkBlock\"><a href=\"{}\">{}</a></div>", url, name));
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+fn categories(links: &[(&str, &str)]) -> String {
+    let mut html = String::from("<html><body><div class=\"visualNavContainer\">");
+    for &(url, name) in links {
+        html.push_str(&format!(
+            "<div><a href=\"{0}\"><img src=\"/img.jpg\"></a><div class=\"categoryContainer\"><a href=\"{0}\">{1}</a></div></div>",
+            url, name));
+    }
+    html.push_str("</div></body></html>");
+    html
+}
+
+fn products(urls: &[&str]) -> String {
+    let mut html = String::from("<html><body><div id=\"productLists\">");
+    for url in urls {
+        html.push_str(&format!("<div class=\"productDetails\"><a href=\"{}\">Product</a></div>", url));
+    }
+    html.push_str("</div></body></html>");
+    html
+}
+
+fn product(item_number: &str, name: &str, typ: &str, price: &str, variants: &[&str]) -> String {
+    let mut links = String::new();
+    for url in variants {
+        links.push_str(&format!("<a href=\"{}\">Variant</a>", url));
+    }
+
+    format!("<html><body>\
+             <img id=\"productImg\" src=\"/PIAimages/{0}.JPG\">\
+             <div id=\"name\">{1}</div><div id=\"type\">{2}</div>\
+             <span id=\"price1\">{3}</span><span class=\"productunit\">/ piece</span>\
+             <div id=\"metric\">90x200 cm</div><div id=\"itemNumber\">{0}</div>\
+             <div id=\"productVariants\">{4}</div>\
+             <div id=\"assemblyInstructions\"><a href=\"/manuals/{0}.pdf\">Assembly instructions</a></div>\
+             </body></html>",
+            item_number, name, typ, price, links)
+}
+
+fn output_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("ikea-spider-{}-{}", process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn read_file(path: &Path) -> String {
+    let mut contents = String::new();
+    File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+    contents
+}
+
+/// Runs one crawl against `site` and returns its stdout.
+fn run_spider(site: &MockSite, countries: &str, output: &Path, args: &[&str]) -> String {
+    run_spider_at(&site.base_address(), countries, output, args)
+}
+
+/// Runs one crawl with `base` as the crawled site and returns its stdout.
+fn run_spider_at(base: &str, countries: &str, output: &Path, args: &[&str]) -> String {
+    let result = Command::new(env!("CARGO_BIN_EXE_ikea-spider-experiment"))
+        .args(["--base-url", base, "-c", countries, "--rate", "0", "--retry-delay", "0", "--retry-jitter", "0"])
+        .arg("-o").arg(output)
+        .args(args)
+        .output()
+        .unwrap();
+
+    assert!(result.status.success());
+    String::from_utf8(result.stdout).unwrap()
+}
+
+#[test]
+fn crawl_writes_csv_for_every_reachable_product() {
+    let site = MockSite::start();
+    let output = output_dir("csv").join("output.csv");
+
+    run_spider(&site, "sg-en", &output, &["--retries", "1"]);
+
+    let csv = read_file(&output);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines, vec![
+        "Item Number,Name,Type,Price,Unit,Metric,Image URL,URL,Department,Category,Subcategory,Department URL,Category URL, Subcategory URL,Price Amount,Price Currency,Regular Price,Discounted Price,Family Price,Offer Valid From,Offer Valid To,Width (cm),Depth (cm),Height (cm),Length (cm),Weight (kg),Volume (l),Images,Parent Item Number,Parent URL,Variant URLs,Buy Online,Stock,New,Last Chance,Description,Key Features,Materials,Care Instructions,Designer,Good to Know,Documents,Packages,Rating,Review Count",
+        "\"19011539\",\"HEMNES\",\"Day-bed frame\",\"$399.00\",\"/ piece\",\"90x200 cm\",\"/PIAimages/190.115.39.JPG\",\"/sg/en/products/S19011539/\",\
+         \"Bedroom\",\"Beds\",\"Double beds\",\"/sg/en/bedroom/\",\"/sg/en/bedroom/beds/\",\"/sg/en/bedroom/beds/double/\",\"399.00\",\"SGD\",\"399.00\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"[{\"\"url\"\":\"\"/PIAimages/190.115.39.JPG\"\",\"\"large\"\":\"\"\"\",\"\"zoom\"\":\"\"\"\",\"\"path\"\":\"\"\"\",\"\"hash\"\":\"\"\"\"}]\",\"\",\"\",\"[]\",\"\",\"\",\"false\",\"false\",\"\",\"[]\",\"\",\"\",\"\",\"\",\"[{\"\"url\"\":\"\"/manuals/190.115.39.pdf\"\",\"\"kind\"\":\"\"assembly\"\",\"\"language\"\":\"\"en\"\",\"\"path\"\":\"\"\"\",\"\"hash\"\":\"\"\"\"}]\",\"[]\",\"\",\"\"",
+        "\"49157218\",\"MALM\",\"Bed frame, high\",\"$299.00\",\"/ piece\",\"90x200 cm\",\"/PIAimages/491.572.18.JPG\",\"/sg/en/products/S49157218/\",\
+         \"Bedroom\",\"Beds\",\"Double beds\",\"/sg/en/bedroom/\",\"/sg/en/bedroom/beds/\",\"/sg/en/bedroom/beds/double/\",\"299.00\",\"SGD\",\"299.00\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"[{\"\"url\"\":\"\"/PIAimages/491.572.18.JPG\"\",\"\"large\"\":\"\"\"\",\"\"zoom\"\":\"\"\"\",\"\"path\"\":\"\"\"\",\"\"hash\"\":\"\"\"\"}]\",\"\",\"\",\"[\"\"/sg/en/products/S79157219/\"\"]\",\"\",\"\",\"false\",\"false\",\"\",\"[]\",\"\",\"\",\"\",\"\",\"[{\"\"url\"\":\"\"/manuals/491.572.18.pdf\"\",\"\"kind\"\":\"\"assembly\"\",\"\"language\"\":\"\"en\"\",\"\"path\"\":\"\"\"\",\"\"hash\"\":\"\"\"\"}]\",\"[]\",\"\",\"\"",
+        "\"79157219\",\"MALM\",\"Bed frame, high, white\",\"$299.00\",\"/ piece\",\"90x200 cm\",\"/PIAimages/791.572.19.JPG\",\"/sg/en/products/S79157219/\",\
+         \"Bedroom\",\"Beds\",\"Double beds\",\"/sg/en/bedroom/\",\"/sg/en/bedroom/beds/\",\"/sg/en/bedroom/beds/double/\",\"299.00\",\"SGD\",\"299.00\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"[{\"\"url\"\":\"\"/PIAimages/791.572.19.JPG\"\",\"\"large\"\":\"\"\"\",\"\"zoom\"\":\"\"\"\",\"\"path\"\":\"\"\"\",\"\"hash\"\":\"\"\"\"}]\",\"49157218\",\"/sg/en/products/S49157218/\",\"[\"\"/sg/en/products/S49157218/\"\"]\",\"\",\"\",\"false\",\"false\",\"\",\"[]\",\"\",\"\",\"\",\"\",\"[{\"\"url\"\":\"\"/manuals/791.572.19.pdf\"\",\"\"kind\"\":\"\"assembly\"\",\"\"language\"\":\"\"en\"\",\"\"path\"\":\"\"\"\",\"\"hash\"\":\"\"\"\"}]\",\"[]\",\"\",\"\"",
+    ]);
+}
+
+#[test]
+fn crawl_reports_broken_links_and_server_errors() {
+    let site = MockSite::start();
+    let output = output_dir("errors").join("output.csv");
+
+    let stdout = run_spider(&site, "sg-en", &output, &["--retries", "1"]);
+    let base = site.base_address();
+
+    assert!(stdout.contains("Errors for Singapore:\n"));
+    assert!(stdout.contains(&format!("Failed to fetch HTML at {}/sg/en/kitchen/\n", base)));
+    assert!(stdout.contains(&format!("Failed to fetch HTML at {}/sg/en/gone/\n", base)));
+    assert!(stdout.contains(&format!("Failed to fetch product data at {}/sg/en/products/S29018576/\n", base)));
+    assert!(stdout.contains(&format!("Failed to fetch product data at {}/sg/en/products/S00000000/\n", base)));
+}
+
+#[test]
+fn server_errors_are_retried_but_broken_links_are_not() {
+    let site = MockSite::start();
+    let output = output_dir("retries").join("output.csv");
+
+    run_spider(&site, "sg-en", &output, &["--retries", "3"]);
+
+    assert_eq!(site.hits("/sg/en/kitchen/"), 3);
+    assert_eq!(site.hits("/sg/en/products/S29018576/"), 3);
+    assert_eq!(site.hits("/sg/en/gone/"), 1);
+    assert_eq!(site.hits("/sg/en/products/S00000000/"), 1);
+}
+
+#[test]
+fn every_country_gets_its_own_output_and_error_report() {
+    let site = MockSite::start();
+    let dir = output_dir("countries");
+
+    let stdout = run_spider(&site, "sg-en,my-en", &dir.join("output-{country}.csv"), &["--retries", "1"]);
+
+    assert!(read_file(&dir.join("output-sg-en.csv")).contains("\"/sg/en/products/S49157218/\""));
+    assert!(read_file(&dir.join("output-my-en.csv")).contains("\"/my/en/products/S49157218/\""));
+    assert!(stdout.contains("Errors for Singapore:\n"));
+    assert!(!stdout.contains("Errors for Malaysia English:\n"));
+}
+
+#[test]
+fn images_are_mirrored_once_and_revalidated_on_later_runs() {
+    let site = MockSite::start();
+    let dir = output_dir("images");
+    let images = dir.join("images");
+    let args = ["--retries", "1", "--images", images.to_str().unwrap()];
+
+    run_spider(&site, "sg-en", &dir.join("output.csv"), &args);
+    run_spider(&site, "sg-en", &dir.join("output.csv"), &args);
+
+    assert_eq!(read_file(&images.join("sg-en/49157218/1.jpg")), "JPEG");
+    assert_eq!(read_file(&images.join("sg-en/19011539/1.jpg")), "JPEG");
+    assert_eq!(fs::read_dir(images.join("objects")).unwrap().count(), 1);
+    assert_eq!(read_file(&images.join("index")).lines().count(), 3);
+    assert_eq!(site.hits("/PIAimages/491.572.18.JPG"), 2);
+    assert!(read_file(&dir.join("output.csv")).contains("\"\"path\"\":\"\"sg-en/49157218/1.jpg\"\""));
+}
+
+#[test]
+fn documents_are_archived_per_market() {
+    let site = MockSite::start();
+    let dir = output_dir("documents");
+    let documents = dir.join("documents");
+
+    run_spider(&site, "sg-en", &dir.join("output.csv"), &["--retries", "1", "--documents", documents.to_str().unwrap()]);
+
+    assert_eq!(read_file(&documents.join("sg-en/49157218/assembly-1.pdf")), "PDF");
+    assert_eq!(read_file(&documents.join("sg-en/79157219/assembly-1.pdf")), "PDF");
+    assert!(read_file(&dir.join("output.csv")).contains("\"\"path\"\":\"\"sg-en/19011539/assembly-1.pdf\"\""));
+}
+
+#[test]
+fn a_recorded_crawl_replays_to_the_same_output_without_the_site() {
+    let site = MockSite::start();
+    let base = site.base_address();
+    let dir = output_dir("replay");
+    let recording = dir.join("recording");
+    let _ = fs::remove_dir_all(&recording);
+
+    run_spider(&site, "sg-en", &dir.join("recorded.csv"), &["--retries", "1", "--record", recording.to_str().unwrap()]);
+    drop(site);
+    run_spider_at(&base, "sg-en", &dir.join("replayed.csv"), &["--retries", "1", "--replay", recording.to_str().unwrap()]);
+
+    let recorded = fs::read(dir.join("recorded.csv")).unwrap();
+    assert!(String::from_utf8_lossy(&recorded).contains("\"/sg/en/products/S49157218/\""));
+    assert_eq!(fs::read(dir.join("replayed.csv")).unwrap(), recorded);
+}
+
+#[test]
+fn a_countries_file_replaces_the_catalogue_and_all_crawls_each_entry() {
+    let site = MockSite::start();
+    let dir = output_dir("catalogue");
+    let catalogue = dir.join("countries.toml");
+    fs::write(&catalogue, "\
+        [[country]]\nname = \"Singapore\"\ncode = \"sg-en\"\nlanguage = \"en\"\npath = \"/sg/en\"\ncurrency = \"SGD\"\nlocale = \"en_SG\"\n\
+        [[country]]\nname = \"Malaysia English\"\ncode = \"my-en\"\nlanguage = \"en\"\npath = \"/my/en\"\ncurrency = \"MYR\"\nlocale = \"en_MY\"\n").unwrap();
+
+    run_spider(&site, "all", &dir.join("output-{country}.csv"), &["--retries", "1", "--countries", catalogue.to_str().unwrap()]);
+
+    assert!(read_file(&dir.join("output-sg-en.csv")).contains("\"/sg/en/products/S49157218/\""));
+    assert!(read_file(&dir.join("output-my-en.csv")).contains("\"/my/en/products/S49157218/\""));
+    assert!(!dir.join("output-my-ms.csv").exists());
+}
+
+#[test]
+fn unknown_country_codes_are_rejected_before_crawling() {
+    let site = MockSite::start();
+    let dir = output_dir("unknown");
+
+    let stdout = run_spider(&site, "sg-en,xx-xx", &dir.join("output-{country}.csv"), &[]);
+
+    assert!(stdout.contains("Unknown country code xx-xx!\n"));
+    assert!(stdout.contains("sg-en: Singapore\n"));
+    assert!(!dir.join("output-sg-en.csv").exists());
+    assert_eq!(site.hits("/sg/en"), 0);
+}
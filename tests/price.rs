@@ -0,0 +1,59 @@
+extern crate ikea_spider_experiment;
+
+use ikea_spider_experiment::country::{default_countries, find_country};
+use ikea_spider_experiment::price::{parse_amount, parse_date, parse_price, Price};
+
+#[test]
+fn amounts_are_read_from_displayed_prices() {
+    assert_eq!(parse_amount("$1,299.00", '.', ','), Some(1299.0));
+    assert_eq!(parse_amount("RM 1.299,00", '.', ','), Some(1299.0));
+    assert_eq!(parse_amount("฿1,290", '.', ','), Some(1290.0));
+    assert_eq!(parse_amount("฿1,290,000", '.', ','), Some(1290000.0));
+    assert_eq!(parse_amount("12,50 €", ',', '.'), Some(12.5));
+    assert_eq!(parse_amount("1.299 €", ',', '.'), Some(1299.0));
+    assert_eq!(parse_amount("1 299,00 €", ',', ' '), Some(1299.0));
+    assert_eq!(parse_amount("$ 9.9", '.', ','), Some(9.9));
+    assert_eq!(parse_amount("Price on request", '.', ','), None);
+}
+
+#[test]
+fn only_the_first_number_is_read() {
+    assert_eq!(parse_amount("$5.99 / 100 pack", '.', ','), Some(5.99));
+    assert_eq!(parse_amount("4.6 out of 5", '.', ','), Some(4.6));
+    assert_eq!(parse_amount("$1,299.00 incl. 7% GST", '.', ','), Some(1299.0));
+    assert_eq!(parse_amount("Price: 12.", '.', ','), Some(12.0));
+}
+
+#[test]
+fn malaysian_bahasa_prices_use_a_decimal_comma() {
+    let countries = default_countries();
+    let malaysia = find_country(&countries, "my-ms").unwrap();
+
+    assert_eq!(parse_price("RM 12,50", malaysia).map(|price| price.amount), Some(12.5));
+    assert_eq!(parse_price("RM 1.299", malaysia).map(|price| price.amount), Some(1299.0));
+    assert_eq!(parse_price("RM 1.299,00", malaysia).map(|price| price.amount), Some(1299.0));
+    assert_eq!(parse_price("RM 1.299", find_country(&countries, "my-en").unwrap()).map(|price| price.amount), Some(1.299));
+}
+
+#[test]
+fn prices_carry_the_country_currency() {
+    let countries = default_countries();
+    let thailand = find_country(&countries, "th-th").unwrap();
+
+    assert_eq!(parse_price("฿12,990", thailand), Some(Price { amount: 12990.0, currency: "THB".to_string() }));
+    assert_eq!(parse_price("", thailand), None);
+}
+
+#[test]
+fn offer_dates_are_read_day_first_or_year_first() {
+    assert_eq!(parse_date("31.01.2019"), Some("2019-01-31".to_string()));
+    assert_eq!(parse_date("1/2/19"), Some("2019-02-01".to_string()));
+    assert_eq!(parse_date("2019-01-31"), Some("2019-01-31".to_string()));
+    assert_eq!(parse_date("31.13.2019"), None);
+    assert_eq!(parse_date("31.02.2019"), None);
+    assert_eq!(parse_date("29.02.2019"), None);
+    assert_eq!(parse_date("29.02.2020"), Some("2020-02-29".to_string()));
+    assert_eq!(parse_date("31.04.2019"), None);
+    assert_eq!(parse_date("00.01.2019"), None);
+    assert_eq!(parse_date("until stocks last"), None);
+}
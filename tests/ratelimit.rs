@@ -0,0 +1,82 @@
+extern crate hyper;
+extern crate ikea_spider_experiment;
+
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+
+use ikea_spider_experiment::fetcher::{Fetcher, Page, Result};
+use ikea_spider_experiment::ratelimit::RateLimitedFetcher;
+
+/// Records when every URL reached it.
+struct Clock<'a> {
+    fetches: &'a Mutex<Vec<(String, Instant)>>,
+}
+
+impl<'a> Fetcher for Clock<'a> {
+    fn fetch(&self, url: &str, _headers: &Headers) -> Result<Page> {
+        self.fetches.lock().unwrap().push((url.to_string(), Instant::now()));
+
+        Ok(Page {
+            status: StatusCode::Ok,
+            headers: Headers::new(),
+            body: Vec::new(),
+        })
+    }
+}
+
+/// Time since `start` of every fetch, in milliseconds.
+fn offsets(fetches: &Mutex<Vec<(String, Instant)>>, start: Instant) -> Vec<u128> {
+    fetches.lock().unwrap().iter().map(|&(_, at)| at.duration_since(start).as_millis()).collect()
+}
+
+#[test]
+fn a_burst_goes_through_then_requests_are_spaced_by_the_rate() {
+    let fetches = Mutex::new(Vec::new());
+    let fetcher = RateLimitedFetcher::new(Clock { fetches: &fetches }, 10.0, 3);
+
+    let start = Instant::now();
+    for _ in 0..5 {
+        fetcher.fetch("http://www.ikea.com/sg/en", &Headers::new()).unwrap();
+    }
+
+    let offsets = offsets(&fetches, start);
+    assert!(offsets[2] < 50, "{:?}", offsets);
+    assert!((90..190).contains(&offsets[3]), "{:?}", offsets);
+    assert!((190..290).contains(&offsets[4]), "{:?}", offsets);
+}
+
+#[test]
+fn idle_time_refills_the_bucket_up_to_the_burst() {
+    let fetches = Mutex::new(Vec::new());
+    let fetcher = RateLimitedFetcher::new(Clock { fetches: &fetches }, 10.0, 2);
+
+    fetcher.fetch("http://www.ikea.com/sg/en", &Headers::new()).unwrap();
+    fetcher.fetch("http://www.ikea.com/sg/en", &Headers::new()).unwrap();
+    // Long enough for five tokens, of which the bucket holds two.
+    sleep(Duration::from_millis(500));
+
+    let start = Instant::now();
+    for _ in 0..3 {
+        fetcher.fetch("http://www.ikea.com/sg/en", &Headers::new()).unwrap();
+    }
+
+    let offsets = offsets(&fetches, start);
+    assert!(offsets[3] < 50, "{:?}", offsets);
+    assert!((90..190).contains(&offsets[4]), "{:?}", offsets);
+}
+
+#[test]
+fn hosts_have_their_own_bucket() {
+    let fetches = Mutex::new(Vec::new());
+    let fetcher = RateLimitedFetcher::new(Clock { fetches: &fetches }, 1.0, 1);
+
+    let start = Instant::now();
+    fetcher.fetch("http://www.ikea.com/sg/en", &Headers::new()).unwrap();
+    fetcher.fetch("http://family.ikea.com/sg/en", &Headers::new()).unwrap();
+
+    assert!(offsets(&fetches, start)[1] < 50);
+}
@@ -0,0 +1,66 @@
+extern crate hyper;
+extern crate ikea_spider_experiment;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+
+use ikea_spider_experiment::fetcher::{Fetcher, Page, Result};
+use ikea_spider_experiment::retry::{RetryFetcher, RetryPolicy};
+
+/// Answers every fetch with 503 and the given `Retry-After`.
+struct Unavailable<'a> {
+    retry_after: &'static str,
+    fetches: &'a AtomicUsize,
+}
+
+impl<'a> Fetcher for Unavailable<'a> {
+    fn fetch(&self, _url: &str, _headers: &Headers) -> Result<Page> {
+        self.fetches.fetch_add(1, Ordering::SeqCst);
+
+        let mut headers = Headers::new();
+        headers.set_raw("Retry-After", vec![self.retry_after.as_bytes().to_vec()]);
+
+        Ok(Page {
+            status: StatusCode::ServiceUnavailable,
+            headers,
+            body: Vec::new(),
+        })
+    }
+}
+
+fn policy(max_delay: Duration) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(0),
+        jitter: Duration::from_millis(0),
+        max_delay,
+        ..RetryPolicy::default()
+    }
+}
+
+#[test]
+fn retry_after_beyond_the_max_delay_is_not_waited_for() {
+    let fetches = AtomicUsize::new(0);
+    let fetcher = RetryFetcher::new(Unavailable { retry_after: "86400", fetches: &fetches }, policy(Duration::from_secs(1)));
+
+    let start = Instant::now();
+    let page = fetcher.fetch("http://www.ikea.com/sg/en", &Headers::new()).unwrap();
+
+    assert_eq!(page.status, StatusCode::ServiceUnavailable);
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn retry_after_within_the_max_delay_is_followed() {
+    let fetches = AtomicUsize::new(0);
+    let fetcher = RetryFetcher::new(Unavailable { retry_after: "0", fetches: &fetches }, policy(Duration::from_secs(1)));
+
+    let page = fetcher.fetch("http://www.ikea.com/sg/en", &Headers::new()).unwrap();
+
+    assert_eq!(page.status, StatusCode::ServiceUnavailable);
+    assert_eq!(fetches.load(Ordering::SeqCst), 3);
+}
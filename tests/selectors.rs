@@ -0,0 +1,85 @@
+extern crate ikea_spider_experiment;
+extern crate kuchiki;
+
+use kuchiki::traits::*;
+
+use ikea_spider_experiment::selectors::{default_profiles, parse_profiles};
+
+#[test]
+fn steps_clean_up_values() {
+    let profiles = parse_profiles(r##"
+        [test.item_number]
+        selector = "#itemNumber"
+        steps = ["trim", "strip_dots"]
+
+        [test.name]
+        selector = "#name"
+        steps = ["trim", "undup", "strip_newlines"]
+
+        [test.review_count]
+        selector = "#reviewCount"
+        steps = ["digits"]
+
+        [test.package_item_number]
+        selector = ".package"
+        read = "content"
+        steps = ["after_colon", "trim", "strip_dots"]
+
+        [test.stock]
+        selector = "#stockInfo"
+        read = "lines"
+        steps = ["join_lines"]
+
+        [test.image]
+        selector = "img"
+        attribute = "src"
+    "##).unwrap();
+    let profile = profiles.get("test").unwrap();
+
+    let document = kuchiki::parse_html().one(r##"
+        <div id="itemNumber"> 491.572.18 </div>
+        <span id="name">MALM
+            bed   frame</span>
+        <span id="reviewCount">(1,204 reviews)</span>
+        <div class="package">Article number: <b>802.145.38</b></div>
+        <div id="stockInfo">In stock at<br>IKEA Tampines</div>
+        <img src="/a.jpg"><img src="/b.jpg">
+    "##);
+
+    assert_eq!(profile.value(&document, "item_number"), Some("49157218".to_string()));
+    assert_eq!(profile.value(&document, "name"), Some("MALM bed frame".to_string()));
+    assert_eq!(profile.value(&document, "review_count"), Some("1204".to_string()));
+    assert_eq!(profile.value(&document, "package_item_number"), Some("80214538".to_string()));
+    assert_eq!(profile.value(&document, "stock"), Some("In stock at IKEA Tampines".to_string()));
+    assert_eq!(profile.value(&document, "image"), Some("/b.jpg".to_string()));
+    assert_eq!(profile.values(&document, "image"), vec!["/a.jpg", "/b.jpg"]);
+    assert_eq!(profile.value(&document, "missing"), None);
+}
+
+#[test]
+fn profiles_inherit_the_default_profile() {
+    let profiles = parse_profiles(r##"
+        [default.price]
+        selector = ".price"
+
+        [th.unit]
+        selector = ".unit"
+    "##).unwrap();
+
+    let builtin = default_profiles().get("default").unwrap();
+    let default = profiles.get("default").unwrap();
+    let th = profiles.get("th").unwrap();
+
+    assert_eq!(default.field("price").unwrap().selector, ".price");
+    assert_eq!(default.field("name"), builtin.field("name"));
+    assert_eq!(th.field("price").unwrap().selector, ".price");
+    assert_eq!(th.field("unit").unwrap().selector, ".unit");
+    assert!(profiles.get("my").is_none());
+}
+
+#[test]
+fn invalid_profiles_are_rejected() {
+    assert!(parse_profiles("[test.price]\nselector = \"#price1[\"\n").is_err());
+    assert!(parse_profiles("[test.price]\nselector = \"#price1\"\nsteps = [\"shout\"]\n").is_err());
+    assert!(parse_profiles("[test.price]\nselector = \"#price1\"\nattr = \"src\"\n").is_err());
+}
@@ -0,0 +1,75 @@
+extern crate hyper;
+extern crate ikea_spider_experiment;
+extern crate url;
+
+use std::env;
+use std::fs;
+use std::process;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::Duration;
+
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+
+use url::Url;
+
+use ikea_spider_experiment::{write_department_products, Output, Site};
+use ikea_spider_experiment::country::{default_countries, find_country};
+use ikea_spider_experiment::fetcher::{Error, Fetcher, Page, Result};
+
+const PRODUCTS: u64 = 6;
+
+/// A department listing products 1 to 6, whose pages answer slower the
+/// earlier they are listed, so workers finish them in reverse.
+struct SlowFirst {
+    finished: Mutex<Vec<String>>,
+}
+
+impl Fetcher for SlowFirst {
+    fn fetch(&self, url: &str, _headers: &Headers) -> Result<Page> {
+        let path = &url["http://www.ikea.com".len()..];
+
+        let body = if path == "/sg/en" {
+            r#"<div class="departmentLinkBlock"><a href="/sg/en/bedroom/">Bedroom</a></div>"#.to_string()
+        } else if path == "/sg/en/bedroom/" {
+            let links: Vec<String> = (1..PRODUCTS + 1)
+                .map(|n| format!(r#"<div class="productDetails"><a href="/sg/en/products/{}/">{}</a></div>"#, n, n))
+                .collect();
+            format!(r#"<div id="productLists">{}</div>"#, links.concat())
+        } else if let Some(n) = path.strip_prefix("/sg/en/products/").and_then(|rest| rest.trim_end_matches('/').parse::<u64>().ok()) {
+            sleep(Duration::from_millis((PRODUCTS + 1 - n) * 40));
+            self.finished.lock().unwrap().push(n.to_string());
+            format!(r#"<div id="itemNumber">{}</div><span id="name">Product {}</span>"#, n, n)
+        } else {
+            return Err(Error::Missing(url.to_string()));
+        };
+
+        Ok(Page {
+            status: StatusCode::Ok,
+            headers: Headers::new(),
+            body: body.into_bytes(),
+        })
+    }
+}
+
+#[test]
+fn products_are_written_in_listing_order_when_workers_finish_out_of_order() {
+    let fetcher = SlowFirst { finished: Mutex::new(Vec::new()) };
+    let site = Site::new(&fetcher, Url::parse("http://www.ikea.com").unwrap());
+    let country = find_country(&default_countries(), "sg-en").unwrap().clone();
+    let output = env::temp_dir().join(format!("ikea-spider-{}-workers.csv", process::id()));
+
+    let mut error_str = String::new();
+    write_department_products(&site, &country, Output::File(output.to_str().unwrap().to_string()), 4, &mut error_str);
+
+    let csv = fs::read_to_string(&output).unwrap();
+    let _ = fs::remove_file(&output);
+
+    let written: Vec<&str> = csv.lines().skip(1).map(|line| line.split(',').next().unwrap().trim_matches('"')).collect();
+    let finished = fetcher.finished.lock().unwrap().clone();
+
+    assert_eq!(error_str, "");
+    assert_eq!(written, vec!["1", "2", "3", "4", "5", "6"]);
+    assert_ne!(finished, vec!["1", "2", "3", "4", "5", "6"]);
+}